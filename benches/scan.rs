@@ -0,0 +1,105 @@
+// benches/scan.rs
+// ساخت لیست برنامه‌ها روی جدول پروسهٔ ساختگی: روش تک‌گذره در برابر روش قبلی
+// (هر برنامهٔ پیش‌فرض × همهٔ پروسه‌ها، و شمارش پنجره‌ها به ازای هر پروسه).
+// `nested` کد قبلی نیست بلکه بازنویسی همان الگوریتم روی ProcInfo است؛ عددش تخمین هزینهٔ آن الگوریتم است،
+// نه اندازه‌گیری خود کد قدیمی (که مستقیم روی sysinfo و EnumWindows کار می‌کرد).
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use lang_switcher_rust::config::Config;
+use lang_switcher_rust::core::{collect_programs, ProcInfo, Program};
+
+const PREDEFINED: [&str; 7] = ["Code.exe", "PyCharm.exe", "chrome.exe", "firefox.exe", "Opera.exe", "WINWORD.EXE", "EXCEL.EXE"];
+
+fn table(n: u32) -> (Vec<ProcInfo>, HashSet<u32>) {
+    let procs: Vec<ProcInfo> = (0..n)
+        .map(|pid| {
+            let name = match pid % 50 {
+                0 => PREDEFINED[(pid / 50) as usize % PREDEFINED.len()].to_string(),
+                _ => format!("app{}.exe", pid % 400),
+            };
+            let exe_path = Some(PathBuf::from(format!("C:/Program Files/{}/{}", pid % 400, name)));
+            ProcInfo { pid, name, exe_path }
+        })
+        .collect();
+    // حدود یک سوم پروسه‌ها پنجرهٔ قابل دیدن دارند
+    let visible = (0..n).filter(|p| p % 3 == 0).collect();
+    (procs, visible)
+}
+
+/// بررسی‌های `should_skip_process` قبلی، با `current_exe` به ازای هر پروسه
+fn old_skip(name: &str, exe: Option<&PathBuf>) -> bool {
+    let blacklist = ["explorer.exe", "dwm.exe", "csrss.exe", "svchost.exe", "sihost.exe", "taskhostw.exe"];
+    let lower = name.to_lowercase();
+    if blacklist.iter().any(|s| *s == lower) {
+        return true;
+    }
+    if let Some(low) = exe.and_then(|e| e.to_str()).map(str::to_lowercase) {
+        if low.contains("/windows/") || low.contains("/system32/") || low.contains("/syswow64/") {
+            return true;
+        }
+    }
+    let me = std::env::current_exe().ok();
+    me.as_deref().and_then(|p| p.file_name()).and_then(|n| n.to_str()).is_some_and(|n| n.eq_ignore_ascii_case(name))
+}
+
+/// بازسازی منطق قبلی؛ `windows()` هزینهٔ EnumWindows را شبیه‌سازی می‌کند
+fn nested(procs: &[ProcInfo], windows: impl Fn() -> HashSet<u32>, cfg: &Config) -> Vec<Program> {
+    let key = |p: &ProcInfo| p.exe_path.as_ref().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_else(|| p.name.to_lowercase());
+    let visible = windows();
+    let mut seen = HashSet::new();
+    let mut progs = Vec::new();
+    for name in PREDEFINED {
+        for p in procs {
+            if p.name.eq_ignore_ascii_case(name) {
+                if !visible.contains(&p.pid) {
+                    continue;
+                }
+                if old_skip(&p.name, p.exe_path.as_ref()) || !seen.insert(key(p)) {
+                    break;
+                }
+                let lang = cfg.lang_for(&p.name).map(str::to_string);
+                progs.push(Program { name: p.name.clone(), lang, exe_path: p.exe_path.clone(), running: true });
+                break;
+            }
+        }
+    }
+    let mut other = Vec::new();
+    for p in procs {
+        if !windows().contains(&p.pid) || p.name.trim().is_empty() || old_skip(&p.name, p.exe_path.as_ref()) {
+            continue;
+        }
+        if seen.insert(key(p)) {
+            other.push((p.name.clone(), p.exe_path.clone()));
+        }
+    }
+    other.sort_by_key(|a| a.0.to_lowercase());
+    for (name, exe_path) in other {
+        let lang = cfg.lang_for(&name).map(str::to_string);
+        progs.push(Program { name, lang, exe_path, running: true });
+    }
+    progs
+}
+
+fn bench(c: &mut Criterion) {
+    let mut cfg = Config::default();
+    cfg.set_lang("chrome.exe", "fa");
+    cfg.set_lang("app7.exe", "en");
+
+    let mut group = c.benchmark_group("program_list");
+    for n in [200u32, 1000] {
+        let (procs, visible) = table(n);
+        group.bench_with_input(BenchmarkId::new("single_pass", n), &procs, |b, procs| {
+            b.iter(|| collect_programs(black_box(procs), &visible, &cfg).len())
+        });
+        group.bench_with_input(BenchmarkId::new("nested", n), &procs, |b, procs| {
+            b.iter(|| nested(black_box(procs), || visible.clone(), &cfg).len())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
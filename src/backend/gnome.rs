@@ -0,0 +1,313 @@
+// src/backend/gnome.rs
+// GNOME Shell / Mutter روی Wayland: پنجرهٔ فعال از D-Bus و چیدمان از تنظیمات org.gnome.desktop.input-sources
+//
+// Mutter پنجره‌ها را به برنامه‌های دیگر نشان نمی‌دهد (Introspect فقط برای کلاینت‌های مجاز است)، پس
+// پنجرهٔ فعال از افزونهٔ «Window Calls» خوانده می‌شود که متد List را روی org.gnome.Shell ارائه می‌کند.
+// تعویض چیدمان یعنی نوشتن `current` و گذاشتن منبع مقصد اول `mru-sources`؛ این کار با ابزار gsettings
+// انجام می‌شود تا همان backend تنظیماتی که GNOME استفاده می‌کند (dconf) به کار برود.
+use std::ffi::OsString;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use regex::Regex;
+use serde_json::Value;
+use sysinfo::{System, SystemExt};
+use zbus::blocking::Connection;
+
+use super::{app_for_pid, ForegroundApp, LayoutBackend};
+use crate::layouts::{Layout, LayoutRegistry};
+
+pub const SHELL_NAME: &str = "org.gnome.Shell";
+pub const WINDOWS_PATH: &str = "/org/gnome/Shell/Extensions/Windows";
+pub const WINDOWS_INTERFACE: &str = "org.gnome.Shell.Extensions.Windows";
+const SCHEMA: &str = "org.gnome.desktop.input-sources";
+
+/// منبع ورودی GNOME، مثل ("xkb", "us+dvorak") یا ("ibus", "anthy")
+pub type InputSource = (String, String);
+
+pub struct GnomeBackend {
+    sys: System,
+    conn: Connection,
+    settings: InputSourceSettings,
+    /// فرایند `gsettings monitor` برای layout_changes؛ با drop شدن backend بسته می‌شود
+    monitor: Option<Child>,
+}
+
+impl GnomeBackend {
+    /// به session bus وصل می‌شود؛ اگر افزونهٔ Window Calls جواب ندهد خطا برمی‌گرداند
+    pub fn connect() -> zbus::Result<Self> {
+        Self::with(Connection::session()?, InputSourceSettings::new())
+    }
+
+    /// برای تست: bus و تنظیمات دلخواه
+    pub fn with(conn: Connection, settings: InputSourceSettings) -> zbus::Result<Self> {
+        let backend = Self { sys: System::new(), conn, settings, monitor: None };
+        backend.call_windows("List", &())?;
+        Ok(backend)
+    }
+
+    fn call_windows<B>(&self, method: &str, body: &B) -> zbus::Result<String>
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+    {
+        let reply = self.conn.call_method(Some(SHELL_NAME), WINDOWS_PATH, Some(WINDOWS_INTERFACE), method, body)?;
+        reply.body().deserialize::<String>()
+    }
+
+    /// فهرست پنجره‌ها به شکل JSON افزونه (id, pid, wm_class, focus, ...)
+    pub fn windows(&self) -> Option<Vec<Value>> {
+        let json = self.call_windows("List", &()).map_err(|e| eprintln!("GNOME window list failed: {}", e)).ok()?;
+        match serde_json::from_str(&json) {
+            Ok(Value::Array(list)) => Some(list),
+            _ => {
+                eprintln!("GNOME window list is not a JSON array");
+                None
+            }
+        }
+    }
+}
+
+impl LayoutBackend for GnomeBackend {
+    fn foreground_app(&mut self) -> Option<ForegroundApp> {
+        let windows = self.windows()?;
+        let win = windows.iter().find(|w| w["focus"].as_bool() == Some(true))?;
+        let pid = win["pid"].as_u64()? as u32;
+        let id = win["id"].as_u64();
+        let mut app = app_for_pid(&mut self.sys, pid).or_else(|| {
+            Some(ForegroundApp {
+                pid,
+                window: None,
+                name: win["wm_class"].as_str()?.to_string(),
+                exe_path: None,
+                args: Vec::new(),
+                title: None,
+            })
+        })?;
+        app.window = id;
+        // نسخه‌های جدید افزونه عنوان را در List ندارند و GetTitle جدا دارند
+        app.title = match win["title"].as_str() {
+            Some(t) => Some(t.to_string()),
+            None => id.and_then(|id| self.call_windows("GetTitle", &(id as u32)).ok()),
+        };
+        Some(app)
+    }
+
+    fn current_layout(&mut self, registry: &LayoutRegistry) -> Option<String> {
+        let source = self.settings.active_source()?;
+        layout_for_source(registry, &source).map(|l| l.tag.clone())
+    }
+
+    fn installed_layouts(&mut self, registry: &LayoutRegistry) -> Vec<String> {
+        let sources = self.settings.sources().unwrap_or_default();
+        sources.iter().filter_map(|s| layout_for_source(registry, s).map(|l| l.tag.clone())).collect()
+    }
+
+    /// فقط بین منابعی که کاربر در تنظیمات GNOME اضافه کرده
+    fn set_layout(&mut self, layout: &Layout) -> bool {
+        let Some(sources) = self.settings.sources() else { return false };
+        let Some(idx) = sources.iter().position(|s| source_matches(s, layout)) else {
+            eprintln!("Layout {} is not an input source in GNOME settings", layout.name);
+            return false;
+        };
+        self.settings.select(&sources, idx)
+    }
+
+    fn layout_changes(&mut self) -> Option<Receiver<()>> {
+        let (child, rx) = self.settings.monitor()?;
+        self.monitor = Some(child);
+        Some(rx)
+    }
+
+    fn window_alive(&mut self, window: u64) -> bool {
+        self.windows().is_none_or(|list| list.iter().any(|w| w["id"].as_u64() == Some(window)))
+    }
+}
+
+impl Drop for GnomeBackend {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.monitor.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// خواندن و نوشتن کلیدهای org.gnome.desktop.input-sources با gsettings
+#[derive(Debug, Clone, Default)]
+pub struct InputSourceSettings {
+    env: Vec<(OsString, OsString)>,
+}
+
+impl InputSourceSettings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// متغیر محیطی برای gsettings، مثلاً GSETTINGS_BACKEND=keyfile در تست‌ها
+    pub fn env(mut self, key: impl Into<OsString>, value: impl Into<OsString>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    fn gsettings(&self, args: &[&str]) -> Option<String> {
+        let out = Command::new("gsettings").args(args).envs(self.env.iter().map(|(k, v)| (k, v))).output();
+        match out {
+            Ok(o) if o.status.success() => Some(String::from_utf8_lossy(&o.stdout).trim().to_string()),
+            Ok(o) => {
+                eprintln!("gsettings {} failed: {}", args.join(" "), String::from_utf8_lossy(&o.stderr).trim());
+                None
+            }
+            Err(e) => {
+                eprintln!("gsettings not available: {}", e);
+                None
+            }
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.gsettings(&["get", SCHEMA, key])
+    }
+
+    fn set(&self, key: &str, value: &str) -> bool {
+        self.gsettings(&["set", SCHEMA, key, value]).is_some()
+    }
+
+    pub fn sources(&self) -> Option<Vec<InputSource>> {
+        self.get("sources").map(|v| parse_sources(&v))
+    }
+
+    pub fn mru_sources(&self) -> Option<Vec<InputSource>> {
+        self.get("mru-sources").map(|v| parse_sources(&v))
+    }
+
+    /// GNOME جدید منبع فعال را اول mru-sources نگه می‌دارد؛ `current` فقط برای نسخه‌های قدیمی
+    pub fn active_source(&self) -> Option<InputSource> {
+        let sources = self.sources()?;
+        if let Some(first) = self.mru_sources().and_then(|m| m.into_iter().next()) {
+            if sources.contains(&first) {
+                return Some(first);
+            }
+        }
+        let current = self.get("current")?;
+        let idx: usize = current.trim_start_matches("uint32").trim().parse().ok()?;
+        sources.into_iter().nth(idx)
+    }
+
+    /// `gsettings monitor` برای هر تغییر یک خط "key: value" چاپ می‌کند؛ فقط current و mru-sources چیدمان فعال را عوض می‌کنند
+    pub fn monitor(&self) -> Option<(Child, Receiver<()>)> {
+        let mut child = Command::new("gsettings")
+            .args(["monitor", SCHEMA])
+            .envs(self.env.iter().map(|(k, v)| (k, v)))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| eprintln!("gsettings monitor failed, polling layouts instead: {}", e))
+            .ok()?;
+        let stdout = child.stdout.take()?;
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { return };
+                let key = line.split(':').next().unwrap_or_default();
+                if matches!(key, "current" | "mru-sources") && tx.send(()).is_err() {
+                    return;
+                }
+            }
+        });
+        Some((child, rx))
+    }
+
+    /// منبع شمارهٔ `idx` را فعال می‌کند
+    pub fn select(&self, sources: &[InputSource], idx: usize) -> bool {
+        let mru = self.mru_sources().unwrap_or_default();
+        let ok = self.set("current", &idx.to_string());
+        ok && self.set("mru-sources", &format_sources(&promote(&mru, sources, idx)))
+    }
+}
+
+/// منبع مقصد اول و بعد بقیه به ترتیب mru قبلی؛ منابعی که در mru نبودند آخر
+fn promote(mru: &[InputSource], sources: &[InputSource], idx: usize) -> Vec<InputSource> {
+    let target = &sources[idx];
+    let mut out = vec![target.clone()];
+    for s in mru.iter().chain(sources) {
+        if sources.contains(s) && !out.contains(s) {
+            out.push(s.clone());
+        }
+    }
+    out
+}
+
+/// "[('xkb', 'us'), ('xkb', 'ir')]" یا "@a(ss) []" از خروجی gsettings
+pub fn parse_sources(text: &str) -> Vec<InputSource> {
+    let re = Regex::new(r"\(\s*'([^']*)'\s*,\s*'([^']*)'\s*\)").unwrap();
+    re.captures_iter(text).map(|c| (c[1].to_string(), c[2].to_string())).collect()
+}
+
+pub fn format_sources(sources: &[InputSource]) -> String {
+    if sources.is_empty() {
+        return "@a(ss) []".to_string();
+    }
+    let items: Vec<String> = sources.iter().map(|(t, id)| format!("('{}', '{}')", t, id)).collect();
+    format!("[{}]", items.join(", "))
+}
+
+/// فقط منابع xkb؛ شناسه "layout+variant" است
+pub fn layout_for_source<'a>(registry: &'a LayoutRegistry, source: &InputSource) -> Option<&'a Layout> {
+    if source.0 != "xkb" {
+        return None;
+    }
+    let (layout, variant) = match source.1.split_once('+') {
+        Some((l, v)) => (l, Some(v)),
+        None => (source.1.as_str(), None),
+    };
+    registry.by_xkb(layout, variant)
+}
+
+fn source_matches(source: &InputSource, layout: &Layout) -> bool {
+    let Some(xkb) = layout.xkb_layout.as_deref() else { return false };
+    let id = match layout.xkb_variant.as_deref() {
+        Some(v) => format!("{}+{}", xkb, v),
+        None => xkb.to_string(),
+    };
+    source.0 == "xkb" && source.1 == id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn src(t: &str, id: &str) -> InputSource {
+        (t.to_string(), id.to_string())
+    }
+
+    #[test]
+    fn gvariant_text_round_trip() {
+        let parsed = parse_sources("[('xkb', 'us'), ('xkb', 'us+dvorak'), ('ibus', 'anthy')]");
+        assert_eq!(parsed, vec![src("xkb", "us"), src("xkb", "us+dvorak"), src("ibus", "anthy")]);
+        assert_eq!(format_sources(&parsed), "[('xkb', 'us'), ('xkb', 'us+dvorak'), ('ibus', 'anthy')]");
+        assert!(parse_sources("@a(ss) []").is_empty());
+        assert_eq!(format_sources(&[]), "@a(ss) []");
+    }
+
+    #[test]
+    fn sources_map_to_registry() {
+        let reg = LayoutRegistry::default();
+        let tag = |s: InputSource| layout_for_source(&reg, &s).map(|l| l.tag.clone());
+        assert_eq!(tag(src("xkb", "ir")).as_deref(), Some("fa"));
+        assert_eq!(tag(src("xkb", "us+dvorak")).as_deref(), Some("en-dvorak"));
+        assert_eq!(tag(src("ibus", "us")), None);
+        assert!(source_matches(&src("xkb", "us+dvorak"), reg.get("en-dvorak").unwrap()));
+        assert!(!source_matches(&src("xkb", "us+dvorak"), reg.get("en").unwrap()));
+    }
+
+    #[test]
+    fn promote_keeps_mru_order() {
+        let sources = vec![src("xkb", "us"), src("xkb", "ir"), src("xkb", "de")];
+        let mru = vec![src("xkb", "ir"), src("xkb", "fr"), src("xkb", "us")];
+        // fr دیگر جزو منابع نیست؛ de در mru نبود و آخر می‌آید
+        assert_eq!(promote(&mru, &sources, 0), vec![src("xkb", "us"), src("xkb", "ir"), src("xkb", "de")]);
+        assert_eq!(promote(&[], &sources, 2), vec![src("xkb", "de"), src("xkb", "us"), src("xkb", "ir")]);
+    }
+}
@@ -0,0 +1,244 @@
+// src/backend/hyprland.rs
+// پنجرهٔ فعال و چیدمان کیبورد روی Hyprland از طریق دو سوکت آن
+//
+// `.socket.sock`: برای هر درخواست یک اتصال؛ فرمان نوشته می‌شود و جواب تا EOF خوانده می‌شود
+// ("j/activewindow"، "j/devices"، "switchxkblayout <keyboard> <n>").
+// `.socket2.sock`: جریان رویداد به شکل خطوط "EVENT>>DATA"، مثلاً "activewindow>>class,title" و "activelayout>>keyboard,name".
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::Value;
+use sysinfo::{System, SystemExt};
+
+use super::sway::layout_for_name;
+use super::{app_for_pid, ForegroundApp, LayoutBackend};
+use crate::layouts::{Layout, LayoutRegistry};
+
+pub struct HyprlandBackend {
+    sys: System,
+    dir: PathBuf,
+    /// رویدادهای activewindow و windowtitle؛ None یعنی رویداد در دسترس نیست و poll می‌کنیم
+    focus_rx: Option<Receiver<()>>,
+    /// رویداد activelayout؛ با اولین layout_changes برداشته می‌شود
+    layout_rx: Option<Receiver<()>>,
+}
+
+/// یک خط از socket2 به (نام رویداد، داده)
+pub fn parse_event(line: &str) -> Option<(&str, &str)> {
+    line.split_once(">>")
+}
+
+/// رویدادهایی که یعنی پنجرهٔ فعال یا عنوانش عوض شده
+pub fn is_focus_event(name: &str) -> bool {
+    matches!(name, "activewindow" | "activewindowv2" | "windowtitle" | "windowtitlev2" | "closewindow")
+}
+
+/// "0x55d0c2a8e6f0" -> عدد؛ شناسهٔ پنجره برای حافظهٔ per-window
+fn parse_address(address: &str) -> Option<u64> {
+    u64::from_str_radix(address.trim_start_matches("0x"), 16).ok()
+}
+
+/// لیست "us,ir" و variant ",dvorak" کنار هم؛ variant خالی یعنی چیدمان پایه
+pub fn keyboard_layouts(keyboard: &Value) -> Vec<(String, String)> {
+    let layouts = keyboard["layout"].as_str().unwrap_or_default();
+    let variants: Vec<&str> = keyboard["variant"].as_str().unwrap_or_default().split(',').collect();
+    layouts
+        .split(',')
+        .filter(|l| !l.is_empty())
+        .enumerate()
+        .map(|(i, l)| (l.trim().to_string(), variants.get(i).unwrap_or(&"").trim().to_string()))
+        .collect()
+}
+
+impl HyprlandBackend {
+    /// پوشهٔ سوکت‌ها از $HYPRLAND_INSTANCE_SIGNATURE؛ نسخه‌های جدید زیر $XDG_RUNTIME_DIR/hypr و قدیمی‌ها زیر /tmp/hypr
+    pub fn connect() -> io::Result<Self> {
+        let sig = std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE")
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HYPRLAND_INSTANCE_SIGNATURE is not set"))?;
+        let dir = dirs::runtime_dir()
+            .map(|d| d.join("hypr").join(&sig))
+            .filter(|d| d.join(".socket.sock").exists())
+            .unwrap_or_else(|| Path::new("/tmp/hypr").join(&sig));
+        Self::connect_to(&dir)
+    }
+
+    pub fn connect_to(dir: &Path) -> io::Result<Self> {
+        let mut backend = Self { sys: System::new(), dir: dir.to_path_buf(), focus_rx: None, layout_rx: None };
+        // مطمئن شو سوکت فرمان جواب می‌دهد
+        backend.request("j/version")?;
+        match Self::subscribe(&dir.join(".socket2.sock")) {
+            Ok((focus, layout)) => {
+                backend.focus_rx = Some(focus);
+                backend.layout_rx = Some(layout);
+            }
+            Err(e) => eprintln!("Hyprland events unavailable, polling instead: {}", e),
+        }
+        Ok(backend)
+    }
+
+    fn subscribe(path: &Path) -> io::Result<(Receiver<()>, Receiver<()>)> {
+        let stream = UnixStream::connect(path)?;
+        let (focus_tx, focus_rx) = mpsc::channel();
+        let (layout_tx, layout_rx) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else { return };
+                match parse_event(&line) {
+                    Some((name, _)) if is_focus_event(name) && focus_tx.send(()).is_err() => return,
+                    // اگر کسی مشترک چیدمان نیست خطای send مهم نیست
+                    Some(("activelayout", _)) => {
+                        let _ = layout_tx.send(());
+                    }
+                    _ => {}
+                }
+            }
+        });
+        Ok((focus_rx, layout_rx))
+    }
+
+    pub fn request(&mut self, command: &str) -> io::Result<String> {
+        let mut stream = UnixStream::connect(self.dir.join(".socket.sock"))?;
+        stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+        stream.write_all(command.as_bytes())?;
+        stream.shutdown(Shutdown::Write)?;
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply)?;
+        Ok(reply)
+    }
+
+    fn request_json(&mut self, command: &str) -> Option<Value> {
+        let reply = self.request(command).map_err(|e| eprintln!("hyprctl {} failed: {}", command, e)).ok()?;
+        serde_json::from_str(&reply).map_err(|e| eprintln!("hyprctl {} returned invalid JSON: {}", command, e)).ok()
+    }
+
+    /// کیبورد اصلی (main) یا اولین کیبورد
+    pub fn main_keyboard(&mut self) -> Option<Value> {
+        let devices = self.request_json("j/devices")?;
+        let keyboards = devices["keyboards"].as_array()?;
+        keyboards.iter().find(|k| k["main"].as_bool() == Some(true)).or(keyboards.first()).cloned()
+    }
+}
+
+impl LayoutBackend for HyprlandBackend {
+    fn foreground_app(&mut self) -> Option<ForegroundApp> {
+        let win = self.request_json("j/activewindow")?;
+        let pid = win["pid"].as_u64().filter(|&p| p > 0)? as u32;
+        let mut app = app_for_pid(&mut self.sys, pid).or_else(|| {
+            Some(ForegroundApp {
+                pid,
+                window: None,
+                name: win["class"].as_str()?.to_string(),
+                exe_path: None,
+                args: Vec::new(),
+                title: None,
+            })
+        })?;
+        app.window = win["address"].as_str().and_then(parse_address);
+        app.title = win["title"].as_str().map(str::to_string);
+        Some(app)
+    }
+
+    fn current_layout(&mut self, registry: &LayoutRegistry) -> Option<String> {
+        let kb = self.main_keyboard()?;
+        // active_layout_index فقط در نسخه‌های جدید هست؛ در غیر این صورت از نام انسانی
+        if let Some(idx) = kb["active_layout_index"].as_u64() {
+            let (layout, variant) = keyboard_layouts(&kb).into_iter().nth(idx as usize)?;
+            return registry.by_xkb(&layout, Some(&variant)).map(|l| l.tag.clone());
+        }
+        layout_for_name(registry, kb["active_keymap"].as_str()?).map(|l| l.tag.clone())
+    }
+
+    fn installed_layouts(&mut self, registry: &LayoutRegistry) -> Vec<String> {
+        let Some(kb) = self.main_keyboard() else { return Vec::new() };
+        keyboard_layouts(&kb)
+            .iter()
+            .filter_map(|(l, v)| registry.by_xkb(l, Some(v)).map(|x| x.tag.clone()))
+            .collect()
+    }
+
+    /// فقط بین چیدمان‌هایی که در input:kb_layout کانفیگ Hyprland آمده‌اند
+    fn set_layout(&mut self, layout: &Layout) -> bool {
+        let Some(kb) = self.main_keyboard() else { return false };
+        let Some(name) = kb["name"].as_str() else { return false };
+        let variant = layout.xkb_variant.as_deref().unwrap_or("");
+        let layouts = keyboard_layouts(&kb);
+        let Some(idx) = layouts.iter().position(|(l, v)| Some(l.as_str()) == layout.xkb_layout.as_deref() && v == variant)
+        else {
+            eprintln!("Layout {} is not in Hyprland's kb_layout", layout.name);
+            return false;
+        };
+        let command = format!("switchxkblayout {} {}", name, idx);
+        match self.request(&command) {
+            Ok(reply) if reply.trim() == "ok" => true,
+            Ok(reply) => {
+                eprintln!("hyprctl {} failed: {}", command, reply.trim());
+                false
+            }
+            Err(e) => {
+                eprintln!("hyprctl {} failed: {}", command, e);
+                false
+            }
+        }
+    }
+
+    fn wait_for_focus_change(&mut self, timeout: Duration) -> bool {
+        let Some(rx) = &self.focus_rx else {
+            thread::sleep(timeout);
+            return true;
+        };
+        match rx.recv_timeout(timeout) {
+            Ok(()) => {
+                while rx.try_recv().is_ok() {}
+                true
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => {
+                eprintln!("Hyprland event socket closed, falling back to polling");
+                self.focus_rx = None;
+                true
+            }
+        }
+    }
+
+    fn layout_changes(&mut self) -> Option<Receiver<()>> {
+        self.layout_rx.take()
+    }
+
+    fn window_alive(&mut self, window: u64) -> bool {
+        let Some(clients) = self.request_json("j/clients") else { return true };
+        clients
+            .as_array()
+            .is_none_or(|list| list.iter().any(|c| c["address"].as_str().and_then(parse_address) == Some(window)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn events_split_on_the_first_separator() {
+        assert_eq!(parse_event("activewindow>>kitty,~/src >> notes"), Some(("activewindow", "kitty,~/src >> notes")));
+        assert_eq!(parse_event("garbage"), None);
+        assert!(is_focus_event("activewindowv2"));
+        assert!(!is_focus_event("workspace"));
+        assert!(!is_focus_event("activelayout"));
+    }
+
+    #[test]
+    fn layouts_pair_with_variants() {
+        let kb = json!({ "layout": "us,ir,us", "variant": ",,dvorak" });
+        let pairs = keyboard_layouts(&kb);
+        assert_eq!(pairs[0], ("us".to_string(), String::new()));
+        assert_eq!(pairs[2], ("us".to_string(), "dvorak".to_string()));
+        // variant کوتاه‌تر از لیست
+        assert_eq!(keyboard_layouts(&json!({ "layout": "us,ir", "variant": "" })).len(), 2);
+        assert_eq!(parse_address("0x55d0c2a8e6f0"), Some(0x55d0_c2a8_e6f0));
+    }
+}
@@ -0,0 +1,434 @@
+// src/backend/kde.rs
+// KDE Plasma: چیدمان از سرویس org.kde.keyboard و پنجرهٔ فعال از یک اسکریپت KWin
+//
+// KWin پنجره‌ها را روی D-Bus اعلام نمی‌کند؛ پس یک اسکریپت کوچک بارگذاری می‌شود که با هر تغییر پنجرهٔ فعال
+// (یا عنوان آن) با callDBus شیء FOCUS_PATH روی همین اتصال را صدا می‌زند. فهرست چیدمان‌ها و چیدمان فعلی
+// از سیگنال‌های layoutListChanged و layoutChanged به‌روز می‌شوند تا هر بار پرسیدن لازم نباشد.
+//
+// Plasma خودش می‌تواند چیدمان را برای هر پنجره/برنامه/دسکتاپ نگه دارد (SwitchMode در kxkbrc)؛ در آن حالت
+// دو طرف با هم می‌جنگند، پس هشدار می‌دهیم و چیدمان را عوض نمی‌کنیم.
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::Value;
+use sysinfo::{System, SystemExt};
+use zbus::blocking::{Connection, MessageIterator};
+use zbus::message::Type as MessageType;
+use zbus::MatchRule;
+
+use super::{app_for_pid, ForegroundApp, LayoutBackend};
+use crate::instance;
+use crate::layouts::{Layout, LayoutRegistry};
+
+pub const KEYBOARD_NAME: &str = "org.kde.keyboard";
+pub const LAYOUTS_PATH: &str = "/Layouts";
+pub const LAYOUTS_INTERFACE: &str = "org.kde.KeyboardLayouts";
+pub const KWIN_NAME: &str = "org.kde.KWin";
+pub const SCRIPTING_PATH: &str = "/Scripting";
+pub const SCRIPTING_INTERFACE: &str = "org.kde.kwin.Scripting";
+pub const FOCUS_PATH: &str = "/LangSwitcher/Focus";
+pub const FOCUS_INTERFACE: &str = "io.github.sepy_dev.LangSwitcher.Focus";
+const PLUGIN_NAME: &str = "langswitcher";
+
+/// callDBus آرگومان‌های عددی را ممکن است double بفرستد؛ همه‌چیز در یک رشتهٔ JSON می‌رود
+const KWIN_SCRIPT: &str = r#"const target = @TARGET@;
+function send(method, payload) {
+    callDBus(target[0], target[1], target[2], method, JSON.stringify(payload));
+}
+function report(w) {
+    if (!w) return;
+    send("Activated", { id: String(w.internalId), pid: w.pid, class: String(w.resourceClass), caption: w.caption });
+}
+const watched = {};
+function track(w) {
+    if (!w || watched[w.internalId]) return;
+    watched[w.internalId] = true;
+    w.captionChanged.connect(function () { if (w.active) report(w); });
+}
+function activated(w) { track(w); report(w); }
+function removed(w) {
+    delete watched[w.internalId];
+    send("Closed", { id: String(w.internalId) });
+}
+if (workspace.windowActivated) {
+    workspace.windowActivated.connect(activated);
+    workspace.windowRemoved.connect(removed);
+    activated(workspace.activeWindow);
+} else {
+    workspace.clientActivated.connect(activated);
+    workspace.clientRemoved.connect(removed);
+    activated(workspace.activeClient);
+}
+"#;
+
+/// SwitchMode در kxkbrc؛ فقط Global با watcher سازگار است
+#[derive(Debug, Clone, PartialEq)]
+pub enum SwitchMode {
+    Global,
+    Desktop,
+    Application,
+    Window,
+}
+
+impl SwitchMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "Global" => Some(Self::Global),
+            "Desktop" => Some(Self::Desktop),
+            "WinClass" => Some(Self::Application),
+            "Window" => Some(Self::Window),
+            _ => None,
+        }
+    }
+}
+
+/// کلید SwitchMode از بخش [Layout]؛ نبودن فایل یا کلید یعنی پیش‌فرض Plasma (Global)
+pub fn read_switch_mode(kxkbrc: &str) -> SwitchMode {
+    let mut in_layout = false;
+    for line in kxkbrc.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_layout = line == "[Layout]";
+        } else if in_layout {
+            if let Some(value) = line.strip_prefix("SwitchMode=") {
+                return SwitchMode::parse(value).unwrap_or(SwitchMode::Global);
+            }
+        }
+    }
+    SwitchMode::Global
+}
+
+/// مسیرهایی که backend می‌خواند یا می‌نویسد؛ در تست‌ها به پوشهٔ موقت اشاره می‌کنند
+#[derive(Debug, Clone)]
+pub struct KdePaths {
+    pub kxkbrc: PathBuf,
+    /// فایل اسکریپت KWin؛ KWin فقط از روی فایل بارگذاری می‌کند
+    pub script: PathBuf,
+}
+
+impl Default for KdePaths {
+    fn default() -> Self {
+        Self {
+            kxkbrc: dirs::config_dir().unwrap_or_default().join("kxkbrc"),
+            script: instance::runtime_file("langswitcher-kwin.js"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Focused {
+    window: u64,
+    pid: u32,
+    class: String,
+    caption: String,
+}
+
+#[derive(Default)]
+struct FocusState {
+    current: Option<Focused>,
+    alive: HashSet<u64>,
+}
+
+/// چیزی که اسکریپت KWin صدا می‌زند
+struct FocusSink {
+    state: Arc<Mutex<FocusState>>,
+    tx: Sender<()>,
+}
+
+fn window_key(internal_id: &str) -> u64 {
+    let mut h = DefaultHasher::new();
+    internal_id.hash(&mut h);
+    h.finish()
+}
+
+#[zbus::interface(name = "io.github.sepy_dev.LangSwitcher.Focus")]
+impl FocusSink {
+    fn activated(&self, payload: &str) {
+        let Ok(v) = serde_json::from_str::<Value>(payload) else { return };
+        let (Some(id), Some(pid)) = (v["id"].as_str(), v["pid"].as_u64()) else { return };
+        let focused = Focused {
+            window: window_key(id),
+            pid: pid as u32,
+            class: v["class"].as_str().unwrap_or_default().to_string(),
+            caption: v["caption"].as_str().unwrap_or_default().to_string(),
+        };
+        let mut st = self.state.lock().unwrap();
+        st.alive.insert(focused.window);
+        st.current = Some(focused);
+        drop(st);
+        let _ = self.tx.send(());
+    }
+
+    fn closed(&self, payload: &str) {
+        let Ok(v) = serde_json::from_str::<Value>(payload) else { return };
+        let Some(id) = v["id"].as_str() else { return };
+        let window = window_key(id);
+        let mut st = self.state.lock().unwrap();
+        st.alive.remove(&window);
+        if st.current.as_ref().is_some_and(|f| f.window == window) {
+            st.current = None;
+        }
+    }
+}
+
+/// (نام کوتاه XKB، variant، نام نمایشی) به همان ترتیبی که setLayout انتظار دارد
+pub type KdeLayout = (String, String, String);
+
+#[derive(Default)]
+struct LayoutState {
+    /// None یعنی باید دوباره از getLayoutsList خواند
+    list: Option<Vec<KdeLayout>>,
+    current: Option<u32>,
+}
+
+pub struct KdeBackend {
+    sys: System,
+    conn: Connection,
+    paths: KdePaths,
+    focus: Arc<Mutex<FocusState>>,
+    focus_rx: Receiver<()>,
+    layouts: Arc<Mutex<LayoutState>>,
+    /// سیگنال layoutChanged؛ با اولین layout_changes برداشته می‌شود
+    layout_rx: Option<Receiver<()>>,
+    /// آخرین SwitchMode که درباره‌اش هشدار داده شد
+    warned_mode: Option<SwitchMode>,
+}
+
+impl KdeBackend {
+    pub fn connect() -> zbus::Result<Self> {
+        Self::with(Connection::session()?, KdePaths::default())
+    }
+
+    /// سرویس کیبورد باید جواب بدهد و اسکریپت KWin بارگذاری شود، وگرنه خطا
+    pub fn with(conn: Connection, paths: KdePaths) -> zbus::Result<Self> {
+        let layouts = Arc::new(Mutex::new(LayoutState::default()));
+        let (layout_tx, layout_rx) = mpsc::channel();
+        Self::watch_layouts(&conn, Arc::clone(&layouts), layout_tx)?;
+
+        let focus = Arc::new(Mutex::new(FocusState::default()));
+        let (tx, focus_rx) = mpsc::channel();
+        conn.object_server().at(FOCUS_PATH, FocusSink { state: Arc::clone(&focus), tx })?;
+
+        let mut backend = Self {
+            sys: System::new(),
+            conn,
+            paths,
+            focus,
+            focus_rx,
+            layouts,
+            layout_rx: Some(layout_rx),
+            warned_mode: None,
+        };
+        backend.layouts.lock().unwrap().current = Some(backend.call_layouts::<_, u32>("getLayout", &())?);
+        backend.load_script()?;
+        backend.check_switch_mode();
+        Ok(backend)
+    }
+
+    fn call_layouts<B, R>(&self, method: &str, body: &B) -> zbus::Result<R>
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+        R: for<'d> zbus::zvariant::DynamicDeserialize<'d>,
+    {
+        let reply = self.conn.call_method(Some(KEYBOARD_NAME), LAYOUTS_PATH, Some(LAYOUTS_INTERFACE), method, body)?;
+        reply.body().deserialize::<R>()
+    }
+
+    fn call_scripting<B, R>(&self, method: &str, body: &B) -> zbus::Result<R>
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+        R: for<'d> zbus::zvariant::DynamicDeserialize<'d>,
+    {
+        let reply = self.conn.call_method(Some(KWIN_NAME), SCRIPTING_PATH, Some(SCRIPTING_INTERFACE), method, body)?;
+        reply.body().deserialize::<R>()
+    }
+
+    /// اشتراک سیگنال‌ها قبل از اولین getLayout تا تغییری بین این دو گم نشود
+    fn watch_layouts(conn: &Connection, state: Arc<Mutex<LayoutState>>, changed: Sender<()>) -> zbus::Result<()> {
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .path(LAYOUTS_PATH)?
+            .interface(LAYOUTS_INTERFACE)?
+            .build();
+        let signals = MessageIterator::for_match_rule(rule, conn, None)?;
+        thread::spawn(move || {
+            for msg in signals {
+                let Ok(msg) = msg else { continue };
+                let member = msg.header().member().map(|m| m.to_string());
+                let mut st = state.lock().unwrap();
+                match member.as_deref() {
+                    Some("layoutChanged") => {
+                        st.current = msg.body().deserialize::<u32>().ok();
+                        // اگر کسی مشترک نیست خطای send مهم نیست
+                        let _ = changed.send(());
+                    }
+                    Some("layoutListChanged") => st.list = None,
+                    _ => {}
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn load_script(&mut self) -> zbus::Result<()> {
+        let unique = self.conn.unique_name().map(|n| n.to_string()).unwrap_or_default();
+        let target = serde_json::json!([unique, FOCUS_PATH, FOCUS_INTERFACE]);
+        let script = KWIN_SCRIPT.replace("@TARGET@", &target.to_string());
+        if let Some(dir) = self.paths.script.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        fs::write(&self.paths.script, script).map_err(|e| zbus::Error::Failure(e.to_string()))?;
+
+        // نسخهٔ جامانده از اجرای قبلی به اتصالی اشاره می‌کند که دیگر نیست
+        let _ = self.call_scripting::<_, bool>("unloadScript", &PLUGIN_NAME);
+        let path = self.paths.script.to_string_lossy().to_string();
+        let id: i32 = self.call_scripting("loadScript", &(path, PLUGIN_NAME))?;
+        if id < 0 {
+            return Err(zbus::Error::Failure("KWin refused to load the focus script".into()));
+        }
+        self.call_scripting::<_, ()>("start", &())
+    }
+
+    pub fn switch_mode(&self) -> SwitchMode {
+        fs::read_to_string(&self.paths.kxkbrc).map(|s| read_switch_mode(&s)).unwrap_or(SwitchMode::Global)
+    }
+
+    /// true یعنی Plasma چیدمان را خودش per-window/per-app/per-desktop نگه نمی‌دارد؛ هشدار فقط وقتی حالت عوض شود
+    fn check_switch_mode(&mut self) -> bool {
+        let mode = self.switch_mode();
+        let compatible = mode == SwitchMode::Global;
+        if !compatible && self.warned_mode.as_ref() != Some(&mode) {
+            eprintln!(
+                "Plasma keeps a layout per {:?} (SwitchMode in {}); LangSwitcher will not switch layouts. \
+                 Set 'Switching policy' to Global in System Settings → Keyboard → Layouts.",
+                mode,
+                self.paths.kxkbrc.display()
+            );
+        }
+        self.warned_mode = Some(mode);
+        compatible
+    }
+
+    pub fn layout_list(&mut self) -> Option<Vec<KdeLayout>> {
+        if let Some(list) = self.layouts.lock().unwrap().list.clone() {
+            return Some(list);
+        }
+        let list: Vec<KdeLayout> =
+            self.call_layouts("getLayoutsList", &()).map_err(|e| eprintln!("KDE getLayoutsList failed: {}", e)).ok()?;
+        self.layouts.lock().unwrap().list = Some(list.clone());
+        Some(list)
+    }
+}
+
+impl Drop for KdeBackend {
+    fn drop(&mut self) {
+        let _ = self.call_scripting::<_, bool>("unloadScript", &PLUGIN_NAME);
+        let _ = fs::remove_file(&self.paths.script);
+    }
+}
+
+fn kde_layout_to_registry<'a>(registry: &'a LayoutRegistry, l: &KdeLayout) -> Option<&'a Layout> {
+    registry.by_xkb(&l.0, Some(&l.1))
+}
+
+impl LayoutBackend for KdeBackend {
+    fn foreground_app(&mut self) -> Option<ForegroundApp> {
+        let focused = self.focus.lock().unwrap().current.clone()?;
+        let mut app = app_for_pid(&mut self.sys, focused.pid).or_else(|| {
+            Some(ForegroundApp {
+                pid: focused.pid,
+                window: None,
+                name: focused.class.clone(),
+                exe_path: None,
+                args: Vec::new(),
+                title: None,
+            })
+        })?;
+        app.window = Some(focused.window);
+        app.title = Some(focused.caption);
+        Some(app)
+    }
+
+    fn current_layout(&mut self, registry: &LayoutRegistry) -> Option<String> {
+        let idx = self.layouts.lock().unwrap().current? as usize;
+        let list = self.layout_list()?;
+        kde_layout_to_registry(registry, list.get(idx)?).map(|l| l.tag.clone())
+    }
+
+    fn installed_layouts(&mut self, registry: &LayoutRegistry) -> Vec<String> {
+        let list = self.layout_list().unwrap_or_default();
+        list.iter().filter_map(|l| kde_layout_to_registry(registry, l).map(|x| x.tag.clone())).collect()
+    }
+
+    fn set_layout(&mut self, layout: &Layout) -> bool {
+        if !self.check_switch_mode() {
+            return false;
+        }
+        let Some(list) = self.layout_list() else { return false };
+        let variant = layout.xkb_variant.as_deref().unwrap_or("");
+        let Some(idx) = list.iter().position(|l| Some(l.0.as_str()) == layout.xkb_layout.as_deref() && l.1 == variant)
+        else {
+            eprintln!("Layout {} is not configured in Plasma", layout.name);
+            return false;
+        };
+        match self.call_layouts::<_, bool>("setLayout", &(idx as u32)) {
+            Ok(ok) => ok,
+            Err(e) => {
+                eprintln!("KDE setLayout failed: {}", e);
+                false
+            }
+        }
+    }
+
+    fn wait_for_focus_change(&mut self, timeout: Duration) -> bool {
+        match self.focus_rx.recv_timeout(timeout) {
+            Ok(()) => {
+                while self.focus_rx.try_recv().is_ok() {}
+                true
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            // FocusSink هنوز در object server است، پس عملاً رخ نمی‌دهد
+            Err(RecvTimeoutError::Disconnected) => {
+                thread::sleep(timeout);
+                true
+            }
+        }
+    }
+
+    fn layout_changes(&mut self) -> Option<Receiver<()>> {
+        self.layout_rx.take()
+    }
+
+    fn window_alive(&mut self, window: u64) -> bool {
+        self.focus.lock().unwrap().alive.contains(&window)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn switch_mode_from_kxkbrc() {
+        assert_eq!(read_switch_mode(""), SwitchMode::Global);
+        let rc = "[Layout]\nLayoutList=us,ir\nSwitchMode=WinClass\nUse=true\n";
+        assert_eq!(read_switch_mode(rc), SwitchMode::Application);
+        // فقط بخش [Layout]
+        assert_eq!(read_switch_mode("[Other]\nSwitchMode=Window\n[Layout]\nUse=true\n"), SwitchMode::Global);
+        assert_eq!(read_switch_mode("[Layout]\nSwitchMode=Window"), SwitchMode::Window);
+    }
+
+    #[test]
+    fn script_targets_the_focus_object() {
+        let target = serde_json::json!([":1.7", FOCUS_PATH, FOCUS_INTERFACE]);
+        let script = KWIN_SCRIPT.replace("@TARGET@", &target.to_string());
+        let first = script.lines().next().unwrap();
+        assert_eq!(first, r#"const target = [":1.7","/LangSwitcher/Focus","io.github.sepy_dev.LangSwitcher.Focus"];"#);
+    }
+}
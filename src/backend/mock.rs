@@ -0,0 +1,112 @@
+// src/backend/mock.rs
+// backend درون‌حافظه‌ای برای تست منطق watcher بدون سیستم پنجره
+use std::collections::HashSet;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use super::{ForegroundApp, LayoutBackend};
+use crate::layouts::{Layout, LayoutRegistry};
+
+#[derive(Debug, Default)]
+pub struct MockBackend {
+    pub foreground: Option<ForegroundApp>,
+    pub layout: Option<String>,
+    pub installed: Vec<String>,
+    /// هر set_layout موفق اینجا ثبت می‌شود
+    pub requests: Vec<String>,
+    /// پنجره‌هایی که «بسته شده‌اند»
+    pub closed: HashSet<u64>,
+    /// این تعداد set_layout بعدی true برمی‌گردانند ولی اثری ندارند (مثل پیامی که پنجره نادیده گرفت)
+    pub ignore_switches: usize,
+    /// اگر layout_changes صدا زده شده باشد
+    notify: Option<Sender<()>>,
+}
+
+impl MockBackend {
+    pub fn new(installed: &[&str]) -> Self {
+        Self {
+            installed: installed.iter().map(|s| s.to_string()).collect(),
+            layout: installed.first().map(|s| s.to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// تغییر پنجرهٔ فعال به یک برنامهٔ ساختگی
+    pub fn focus(&mut self, pid: u32, name: &str) {
+        self.foreground = Some(ForegroundApp {
+            pid,
+            window: None,
+            name: name.to_string(),
+            exe_path: None,
+            args: Vec::new(),
+            title: None,
+        });
+    }
+
+    /// مثل focus، ولی با شناسهٔ پنجره
+    pub fn focus_window(&mut self, pid: u32, name: &str, window: u64) {
+        self.focus(pid, name);
+        if let Some(app) = &mut self.foreground {
+            app.window = Some(window);
+        }
+    }
+
+    /// عوض شدن عنوان پنجرهٔ فعال بدون عوض شدن برنامه (مثلاً تب مرورگر)
+    pub fn set_title(&mut self, title: &str) {
+        if let Some(app) = &mut self.foreground {
+            app.title = Some(title.to_string());
+        }
+    }
+
+    /// مثل focus ولی با مسیر exe و آرگومان، برای تست قوانین مسیر/regex
+    pub fn focus_app(&mut self, app: ForegroundApp) {
+        self.foreground = Some(app);
+    }
+
+    /// کاربر خودش چیدمان را عوض می‌کند؛ مشترک layout_changes خبردار می‌شود
+    pub fn switch_manually(&mut self, tag: &str) {
+        self.layout = Some(tag.to_string());
+        if let Some(tx) = &self.notify {
+            let _ = tx.send(());
+        }
+    }
+}
+
+impl LayoutBackend for MockBackend {
+    fn foreground_app(&mut self) -> Option<ForegroundApp> {
+        self.foreground.clone()
+    }
+
+    fn current_layout(&mut self, _registry: &LayoutRegistry) -> Option<String> {
+        self.layout.clone()
+    }
+
+    fn installed_layouts(&mut self, _registry: &LayoutRegistry) -> Vec<String> {
+        self.installed.clone()
+    }
+
+    fn window_alive(&mut self, window: u64) -> bool {
+        !self.closed.contains(&window)
+    }
+
+    fn set_layout(&mut self, layout: &Layout) -> bool {
+        if !self.installed.iter().any(|t| t.eq_ignore_ascii_case(&layout.tag)) {
+            return false;
+        }
+        self.requests.push(layout.tag.clone());
+        if self.ignore_switches > 0 {
+            self.ignore_switches -= 1;
+            return true;
+        }
+        self.layout = Some(layout.tag.clone());
+        if let Some(tx) = &self.notify {
+            let _ = tx.send(());
+        }
+        true
+    }
+
+    fn layout_changes(&mut self) -> Option<Receiver<()>> {
+        let (tx, rx) = mpsc::channel();
+        self.notify = Some(tx);
+        Some(rx)
+    }
+}
@@ -0,0 +1,88 @@
+// src/backend/mod.rs
+// هر پلتفرم یک پیاده‌سازی از LayoutBackend دارد؛ watcher فقط با این trait کار می‌کند
+use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
+use std::thread;
+use std::time::Duration;
+
+use sysinfo::{Pid, PidExt, ProcessExt, System, SystemExt};
+
+use crate::layouts::{Layout, LayoutRegistry};
+
+#[cfg(target_os = "linux")]
+pub mod gnome;
+#[cfg(target_os = "linux")]
+pub mod hyprland;
+#[cfg(target_os = "linux")]
+pub mod kde;
+pub mod mock;
+#[cfg(target_os = "linux")]
+pub mod sway;
+#[cfg(target_os = "windows")]
+pub mod windows;
+#[cfg(target_os = "linux")]
+pub mod x11;
+
+/// برنامه‌ای که پنجرهٔ فعال مال اوست
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForegroundApp {
+    pub pid: u32,
+    /// شناسهٔ پنجرهٔ سطح بالا (HWND یا X11 window id)، برای حافظهٔ per-window
+    pub window: Option<u64>,
+    pub name: String,
+    pub exe_path: Option<PathBuf>,
+    /// آرگومان‌های خط فرمان، بدون خود exe
+    pub args: Vec<String>,
+    /// عنوان پنجرهٔ فعال؛ تب‌های مرورگر با همین از هم جدا می‌شوند
+    pub title: Option<String>,
+}
+
+pub trait LayoutBackend {
+    fn foreground_app(&mut self) -> Option<ForegroundApp>;
+
+    /// تگ چیدمان فعلی، اگر در جدول چیدمان‌ها پیدا شود
+    fn current_layout(&mut self, registry: &LayoutRegistry) -> Option<String>;
+
+    /// تگ چیدمان‌هایی که روی سیستم نصب‌اند
+    fn installed_layouts(&mut self, registry: &LayoutRegistry) -> Vec<String>;
+
+    fn set_layout(&mut self, layout: &Layout) -> bool;
+
+    /// تا رسیدن رویداد تغییر پنجرهٔ فعال (یا عنوان آن) یا تمام شدن timeout صبر می‌کند.
+    /// true یعنی باید دوباره پنجرهٔ فعال را بررسی کرد؛ backendهایی که رویداد ندارند
+    /// فقط می‌خوابند و همیشه true برمی‌گردانند (همان polling قدیمی).
+    fn wait_for_focus_change(&mut self, timeout: Duration) -> bool {
+        thread::sleep(timeout);
+        true
+    }
+
+    /// آیا پنجره هنوز وجود دارد؛ پیش‌فرض true (حافظهٔ per-window آنگاه فقط با سقف اندازه کوچک می‌شود)
+    fn window_alive(&mut self, _window: u64) -> bool {
+        true
+    }
+
+    /// اشتراک تغییر چیدمان (دستی یا از طرف خود watcher)؛ مقدار تازه را `current_layout` می‌دهد.
+    /// فقط یک بار صدا زده می‌شود. None یعنی backend خبر نمی‌دهد و watcher هر دور خودش می‌پرسد؛
+    /// ویندوز همین‌طور است چون برای عوض شدن چیدمان thread دیگر رویدادی (حتی WinEvent) ندارد.
+    fn layout_changes(&mut self) -> Option<Receiver<()>> {
+        None
+    }
+}
+
+/// نام و مسیر exe یک pid؛ فقط همان یک پروسه رفرش می‌شود
+pub fn app_for_pid(sys: &mut System, pid: u32) -> Option<ForegroundApp> {
+    let pid_ = Pid::from_u32(pid);
+    if !sys.refresh_process(pid_) {
+        return None;
+    }
+    let proc_ = sys.process(pid_)?;
+    let exe = proc_.exe();
+    Some(ForegroundApp {
+        pid,
+        window: None,
+        name: proc_.name().to_string(),
+        exe_path: if exe.as_os_str().is_empty() { None } else { Some(exe.to_path_buf()) },
+        args: proc_.cmd().iter().skip(1).cloned().collect(),
+        title: None,
+    })
+}
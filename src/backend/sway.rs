@@ -0,0 +1,323 @@
+// src/backend/sway.rs
+// پنجرهٔ فعال و چیدمان کیبورد روی Sway از طریق سوکت IPC (همان پروتکل i3)
+//
+// پیام: "i3-ipc" + طول payload (u32) + نوع (u32)، هر دو با ترتیب بایت ماشین، و بعد JSON.
+// تغییر فوکوس از رویداد `window`، تغییر چیدمان از رویداد `input` و تعویض چیدمان با `input type:keyboard xkb_switch_layout <n>`.
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::Value;
+use sysinfo::{System, SystemExt};
+
+use super::{app_for_pid, ForegroundApp, LayoutBackend};
+use crate::layouts::{Layout, LayoutRegistry};
+
+const MAGIC: &[u8; 6] = b"i3-ipc";
+const RUN_COMMAND: u32 = 0;
+const SUBSCRIBE: u32 = 2;
+const GET_TREE: u32 = 4;
+const GET_INPUTS: u32 = 100;
+/// نوع رویدادها بیت بالا را دارد
+const EVENT_WINDOW: u32 = 0x8000_0003;
+const EVENT_INPUT: u32 = 0x8000_0015;
+
+pub struct SwayBackend {
+    sys: System,
+    conn: UnixStream,
+    /// رویداد window (focus/title/close)؛ None یعنی رویداد در دسترس نیست و poll می‌کنیم
+    focus_rx: Option<Receiver<()>>,
+    /// رویداد input با change=xkb_layout؛ با اولین layout_changes برداشته می‌شود
+    layout_rx: Option<Receiver<()>>,
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+fn send(stream: &mut impl Write, kind: u32, payload: &[u8]) -> io::Result<()> {
+    let mut buf = Vec::with_capacity(14 + payload.len());
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    buf.extend_from_slice(&kind.to_ne_bytes());
+    buf.extend_from_slice(payload);
+    stream.write_all(&buf)
+}
+
+fn recv(stream: &mut impl Read) -> io::Result<(u32, Value)> {
+    let mut header = [0u8; 14];
+    stream.read_exact(&mut header)?;
+    if &header[..6] != MAGIC {
+        return Err(invalid("not an i3-ipc message"));
+    }
+    let len = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]) as usize;
+    let kind = u32::from_ne_bytes([header[10], header[11], header[12], header[13]]);
+    let mut payload = vec![0; len];
+    stream.read_exact(&mut payload)?;
+    let value = serde_json::from_slice(&payload).map_err(|e| invalid(e.to_string()))?;
+    Ok((kind, value))
+}
+
+impl SwayBackend {
+    /// به سوکت $SWAYSOCK وصل می‌شود
+    pub fn connect() -> io::Result<Self> {
+        let path = std::env::var_os("SWAYSOCK")
+            .map(PathBuf::from)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "SWAYSOCK is not set"))?;
+        Self::connect_to(&path)
+    }
+
+    pub fn connect_to(path: &Path) -> io::Result<Self> {
+        let conn = UnixStream::connect(path)?;
+        conn.set_read_timeout(Some(Duration::from_secs(2)))?;
+        let (focus_rx, layout_rx) = match Self::subscribe(path) {
+            Ok((focus, layout)) => (Some(focus), Some(layout)),
+            Err(e) => {
+                eprintln!("Sway events unavailable, polling instead: {}", e);
+                (None, None)
+            }
+        };
+        Ok(Self { sys: System::new(), conn, focus_rx, layout_rx })
+    }
+
+    /// اتصال جدا برای رویدادها؛ بعد از subscribe روی آن فقط رویداد می‌آید
+    fn subscribe(path: &Path) -> io::Result<(Receiver<()>, Receiver<()>)> {
+        let mut stream = UnixStream::connect(path)?;
+        send(&mut stream, SUBSCRIBE, br#"["window","input"]"#)?;
+        let (_, reply) = recv(&mut stream)?;
+        if reply["success"].as_bool() != Some(true) {
+            return Err(invalid(format!("subscribe refused: {}", reply)));
+        }
+        let (focus_tx, focus_rx) = mpsc::channel();
+        let (layout_tx, layout_rx) = mpsc::channel();
+        thread::spawn(move || loop {
+            match recv(&mut stream) {
+                Ok((EVENT_WINDOW, ev)) => {
+                    if matches!(ev["change"].as_str(), Some("focus" | "title" | "close")) && focus_tx.send(()).is_err() {
+                        return;
+                    }
+                }
+                // اگر کسی مشترک چیدمان نیست خطای send مهم نیست
+                Ok((EVENT_INPUT, ev)) if ev["change"] == "xkb_layout" => {
+                    let _ = layout_tx.send(());
+                }
+                Ok(_) => {}
+                Err(_) => return,
+            }
+        });
+        Ok((focus_rx, layout_rx))
+    }
+
+    fn request(&mut self, kind: u32, payload: &str) -> io::Result<Value> {
+        send(&mut self.conn, kind, payload.as_bytes())?;
+        let (reply_kind, value) = recv(&mut self.conn)?;
+        if reply_kind != kind {
+            return Err(invalid(format!("expected reply type {}, got {}", kind, reply_kind)));
+        }
+        Ok(value)
+    }
+
+    pub fn tree(&mut self) -> Option<Value> {
+        self.request(GET_TREE, "").map_err(|e| eprintln!("sway get_tree failed: {}", e)).ok()
+    }
+
+    /// نام انسانی چیدمان‌های اولین کیبورد و شمارهٔ چیدمان فعال
+    pub fn keyboard_layouts(&mut self) -> Option<(Vec<String>, Option<usize>)> {
+        let inputs = self.request(GET_INPUTS, "").map_err(|e| eprintln!("sway get_inputs failed: {}", e)).ok()?;
+        let kb = inputs.as_array()?.iter().find(|i| {
+            i["type"] == "keyboard" && i["xkb_layout_names"].as_array().is_some_and(|n| !n.is_empty())
+        })?;
+        let names = kb["xkb_layout_names"].as_array()?.iter().filter_map(|n| n.as_str().map(str::to_string)).collect();
+        let active = kb["xkb_active_layout_index"].as_u64().map(|i| i as usize);
+        Some((names, active))
+    }
+
+    pub fn run_command(&mut self, command: &str) -> bool {
+        match self.request(RUN_COMMAND, command) {
+            Ok(Value::Array(results)) => results.iter().all(|r| r["success"].as_bool() == Some(true)),
+            Ok(other) => {
+                eprintln!("sway command '{}' unexpected reply: {}", command, other);
+                false
+            }
+            Err(e) => {
+                eprintln!("sway command '{}' failed: {}", command, e);
+                false
+            }
+        }
+    }
+}
+
+/// پنجره‌ای که `focused` دارد؛ workspace خالی فوکوس‌دار پنجره حساب نمی‌شود
+fn focused_node(node: &Value) -> Option<&Value> {
+    if node["focused"].as_bool() == Some(true) {
+        return node["pid"].is_u64().then_some(node);
+    }
+    children(node).find_map(focused_node)
+}
+
+fn children(node: &Value) -> impl Iterator<Item = &Value> {
+    ["nodes", "floating_nodes"].into_iter().flat_map(move |k| node[k].as_array().into_iter().flatten())
+}
+
+fn contains_node(node: &Value, id: u64) -> bool {
+    node["id"].as_u64() == Some(id) || children(node).any(|c| contains_node(c, id))
+}
+
+/// "English (US)" -> "English"
+fn base_name(name: &str) -> &str {
+    name.split(" (").next().unwrap_or(name).trim()
+}
+
+/// نام انسانی XKB ("Persian"، "English (US)") را با جدول تطبیق می‌دهد: اول دقیق، بعد فقط نام زبان اگر یکتا باشد
+pub fn layout_for_name<'a>(registry: &'a LayoutRegistry, name: &str) -> Option<&'a Layout> {
+    if let Some(l) = registry.all().iter().find(|l| l.name.eq_ignore_ascii_case(name)) {
+        return Some(l);
+    }
+    let mut same_base = registry.all().iter().filter(|l| base_name(&l.name).eq_ignore_ascii_case(base_name(name)));
+    match (same_base.next(), same_base.next()) {
+        (Some(l), None) => Some(l),
+        _ => None,
+    }
+}
+
+/// شمارهٔ چیدمان در لیست کیبورد؛ برای چیدمان با variant (مثل dvorak) فقط تطبیق دقیق
+fn layout_index(names: &[String], layout: &Layout) -> Option<usize> {
+    if let Some(i) = names.iter().position(|n| n.eq_ignore_ascii_case(&layout.name)) {
+        return Some(i);
+    }
+    if layout.xkb_variant.is_some() {
+        return None;
+    }
+    let mut same_base = names.iter().enumerate().filter(|(_, n)| base_name(n).eq_ignore_ascii_case(base_name(&layout.name)));
+    match (same_base.next(), same_base.next()) {
+        (Some((i, _)), None) => Some(i),
+        _ => None,
+    }
+}
+
+impl LayoutBackend for SwayBackend {
+    fn foreground_app(&mut self) -> Option<ForegroundApp> {
+        let tree = self.tree()?;
+        let node = focused_node(&tree)?;
+        let pid = node["pid"].as_u64()? as u32;
+        // برنامهٔ Wayland نام exe را از pid می‌گیرد؛ اگر پروسه دیده نشد app_id یا کلاس Xwayland
+        let mut app = app_for_pid(&mut self.sys, pid).or_else(|| {
+            let name = node["app_id"].as_str().or_else(|| node["window_properties"]["class"].as_str())?;
+            Some(ForegroundApp {
+                pid,
+                window: None,
+                name: name.to_string(),
+                exe_path: None,
+                args: Vec::new(),
+                title: None,
+            })
+        })?;
+        app.window = node["id"].as_u64();
+        app.title = node["name"].as_str().map(str::to_string);
+        Some(app)
+    }
+
+    fn current_layout(&mut self, registry: &LayoutRegistry) -> Option<String> {
+        let (names, active) = self.keyboard_layouts()?;
+        let name = names.get(active?)?;
+        layout_for_name(registry, name).map(|l| l.tag.clone())
+    }
+
+    fn installed_layouts(&mut self, registry: &LayoutRegistry) -> Vec<String> {
+        let Some((names, _)) = self.keyboard_layouts() else { return Vec::new() };
+        names.iter().filter_map(|n| layout_for_name(registry, n).map(|l| l.tag.clone())).collect()
+    }
+
+    /// فقط بین چیدمان‌هایی که در کانفیگ sway برای کیبورد تعریف شده‌اند
+    fn set_layout(&mut self, layout: &Layout) -> bool {
+        let Some((names, _)) = self.keyboard_layouts() else { return false };
+        let Some(idx) = layout_index(&names, layout) else {
+            eprintln!("Layout {} is not configured in sway (have: {})", layout.name, names.join(", "));
+            return false;
+        };
+        self.run_command(&format!("input type:keyboard xkb_switch_layout {}", idx))
+    }
+
+    fn wait_for_focus_change(&mut self, timeout: Duration) -> bool {
+        let Some(rx) = &self.focus_rx else {
+            thread::sleep(timeout);
+            return true;
+        };
+        match rx.recv_timeout(timeout) {
+            Ok(()) => {
+                while rx.try_recv().is_ok() {}
+                true
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => {
+                eprintln!("Sway event connection closed, falling back to polling");
+                self.focus_rx = None;
+                true
+            }
+        }
+    }
+
+    fn layout_changes(&mut self) -> Option<Receiver<()>> {
+        self.layout_rx.take()
+    }
+
+    fn window_alive(&mut self, window: u64) -> bool {
+        // اگر درخت در دسترس نیست فرض کن زنده است تا حافظه بی‌دلیل پاک نشود
+        self.tree().is_none_or(|t| contains_node(&t, window))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn framing_round_trip() {
+        let mut buf = Vec::new();
+        send(&mut buf, GET_TREE, b"{\"a\":1}").unwrap();
+        assert_eq!(&buf[..6], b"i3-ipc");
+        assert_eq!(buf.len(), 14 + 7);
+        let (kind, v) = recv(&mut buf.as_slice()).unwrap();
+        assert_eq!((kind, v), (GET_TREE, json!({ "a": 1 })));
+        assert!(recv(&mut &b"i3-ipX\0\0\0\0\0\0\0\0"[..]).is_err());
+    }
+
+    #[test]
+    fn focused_window_is_found_in_floating_nodes() {
+        let tree = json!({
+            "id": 1, "focused": false, "nodes": [
+                { "id": 2, "type": "workspace", "focused": false, "nodes": [
+                    { "id": 3, "pid": 10, "focused": false, "nodes": [] }
+                ], "floating_nodes": [
+                    { "id": 4, "pid": 11, "focused": true, "app_id": "foot" }
+                ] }
+            ]
+        });
+        assert_eq!(focused_node(&tree).unwrap()["id"], 4);
+        assert!(contains_node(&tree, 3));
+        assert!(!contains_node(&tree, 9));
+
+        let empty_ws = json!({ "id": 1, "nodes": [{ "id": 2, "type": "workspace", "focused": true }] });
+        assert!(focused_node(&empty_ws).is_none());
+    }
+
+    #[test]
+    fn xkb_names_map_to_registry() {
+        let reg = LayoutRegistry::default();
+        let tag = |n| layout_for_name(&reg, n).map(|l| l.tag.as_str());
+        assert_eq!(tag("English (US)"), Some("en"));
+        assert_eq!(tag("Persian"), Some("fa"));
+        assert_eq!(tag("Arabic"), Some("ar"));
+        // "English" هم en است هم en-dvorak
+        assert_eq!(tag("English (UK)"), None);
+
+        let names: Vec<String> = ["English (US)", "Persian"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(layout_index(&names, reg.get("fa").unwrap()), Some(1));
+        assert_eq!(layout_index(&names, reg.get("en-dvorak").unwrap()), None);
+        assert_eq!(layout_index(&names, reg.get("ru").unwrap()), None);
+    }
+}
@@ -0,0 +1,311 @@
+// src/backend/windows.rs
+use std::cell::Cell;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use sysinfo::{System, SystemExt};
+use widestring::U16CString;
+use winapi::shared::minwindef::{DWORD, FALSE, HKL, LPARAM, WPARAM};
+use winapi::shared::windef::{HWINEVENTHOOK, HWND};
+use winapi::um::winnt::LONG;
+use winapi::um::winuser::{
+    DispatchMessageW, GetForegroundWindow, GetKeyboardLayout, GetKeyboardLayoutList,
+    GetWindowTextLengthW, GetWindowTextW, GetWindowThreadProcessId, IsWindow, LoadKeyboardLayoutW,
+    MsgWaitForMultipleObjects, PeekMessageW, PostMessageW, SetWinEventHook, TranslateMessage,
+    UnhookWinEvent, CHILDID_SELF, EVENT_OBJECT_NAMECHANGE, EVENT_SYSTEM_FOREGROUND, KLF_NOTELLSHELL,
+    MSG, OBJID_WINDOW, PM_REMOVE, QS_ALLINPUT, WINEVENT_OUTOFCONTEXT, WINEVENT_SKIPOWNPROCESS,
+    WM_INPUTLANGCHANGEREQUEST,
+};
+
+use super::{app_for_pid, ForegroundApp, LayoutBackend};
+use crate::layouts::{Layout, LayoutRegistry};
+
+pub struct WindowsBackend {
+    sys: System,
+    /// hookها روی همان threadی نصب می‌شوند که wait_for_focus_change را صدا می‌زند
+    hooks: Vec<HWINEVENTHOOK>,
+    hook_failed: bool,
+    /// HKL هر چیدمان رجیستری؛ با عوض شدن رجیستری (کانفیگ) دوباره ساخته می‌شود
+    hkls: Option<(LayoutRegistry, HklMap)>,
+}
+
+/// (HKL, تگ) به ترتیب رجیستری
+type HklMap = Vec<(usize, String)>;
+
+type WinEventCallback = unsafe extern "system" fn(HWINEVENTHOOK, DWORD, HWND, LONG, LONG, DWORD, DWORD);
+
+thread_local! {
+    static FOCUS_CHANGED: Cell<bool> = const { Cell::new(false) };
+}
+
+unsafe extern "system" fn on_foreground(
+    _hook: HWINEVENTHOOK,
+    _event: DWORD,
+    _hwnd: HWND,
+    _id_object: LONG,
+    _id_child: LONG,
+    _thread: DWORD,
+    _time: DWORD,
+) {
+    FOCUS_CHANGED.with(|f| f.set(true));
+}
+
+/// NAMECHANGE برای همهٔ اشیای UI می‌آید؛ فقط عنوان خود پنجرهٔ فعال مهم است
+unsafe extern "system" fn on_name_change(
+    _hook: HWINEVENTHOOK,
+    _event: DWORD,
+    hwnd: HWND,
+    id_object: LONG,
+    id_child: LONG,
+    _thread: DWORD,
+    _time: DWORD,
+) {
+    if id_object == OBJID_WINDOW && id_child == CHILDID_SELF && hwnd == GetForegroundWindow() {
+        FOCUS_CHANGED.with(|f| f.set(true));
+    }
+}
+
+impl Drop for WindowsBackend {
+    fn drop(&mut self) {
+        for hook in self.hooks.drain(..) {
+            unsafe { UnhookWinEvent(hook) };
+        }
+    }
+}
+
+impl Default for WindowsBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WindowsBackend {
+    pub fn new() -> Self {
+        Self { sys: System::new(), hooks: Vec::new(), hook_failed: false, hkls: None }
+    }
+
+    fn hkl_map(&mut self, registry: &LayoutRegistry) -> &HklMap {
+        if self.hkls.as_ref().is_none_or(|(r, _)| r != registry) {
+            let map = build_hkl_map(registry, load_hkl);
+            self.hkls = Some((registry.clone(), map));
+        }
+        &self.hkls.as_ref().unwrap().1
+    }
+
+    fn hkl_to_tag(&mut self, registry: &LayoutRegistry, hkl: HKL) -> Option<String> {
+        tag_for_hkl(self.hkl_map(registry), hkl as usize)
+    }
+
+    /// EVENT_SYSTEM_FOREGROUND (و NAMECHANGE برای عوض شدن تب)؛ اگر نصب نشد به polling برمی‌گردیم
+    fn ensure_hook(&mut self) -> bool {
+        if !self.hooks.is_empty() {
+            return true;
+        }
+        if self.hook_failed {
+            return false;
+        }
+        let install = |event: DWORD, callback: WinEventCallback| unsafe {
+            SetWinEventHook(
+                event,
+                event,
+                std::ptr::null_mut(),
+                Some(callback),
+                0,
+                0,
+                WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+            )
+        };
+        let hook = install(EVENT_SYSTEM_FOREGROUND, on_foreground);
+        if hook.is_null() {
+            eprintln!("SetWinEventHook failed, polling foreground window instead");
+            self.hook_failed = true;
+            return false;
+        }
+        self.hooks.push(hook);
+        // بدون این hook فقط تغییر برنامه دیده می‌شود و عوض شدن تب تا poll_ms بعدی می‌ماند
+        let names = install(EVENT_OBJECT_NAMECHANGE, on_name_change);
+        if names.is_null() {
+            eprintln!("Window title events unavailable");
+        } else {
+            self.hooks.push(names);
+        }
+        true
+    }
+
+    /// پیام‌های thread را پردازش می‌کند تا callback هوک اجرا شود
+    fn pump_messages() {
+        unsafe {
+            let mut msg: MSG = std::mem::zeroed();
+            while PeekMessageW(&mut msg, std::ptr::null_mut(), 0, 0, PM_REMOVE) != 0 {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+    }
+
+    fn window_title(hwnd: HWND) -> Option<String> {
+        unsafe {
+            let len = GetWindowTextLengthW(hwnd);
+            if len <= 0 {
+                return None;
+            }
+            let mut buf: Vec<u16> = vec![0; len as usize + 1];
+            let n = GetWindowTextW(hwnd, buf.as_mut_ptr(), buf.len() as i32);
+            (n > 0).then(|| String::from_utf16_lossy(&buf[..n as usize]))
+        }
+    }
+
+    fn foreground_pid() -> Option<u32> {
+        unsafe {
+            let hwnd: HWND = GetForegroundWindow();
+            if hwnd.is_null() {
+                return None;
+            }
+            let mut pid: DWORD = 0;
+            GetWindowThreadProcessId(hwnd, &mut pid as *mut u32);
+            if pid == 0 {
+                None
+            } else {
+                Some(pid)
+            }
+        }
+    }
+}
+
+/// HKL واقعی یک KLID؛ بدون KLF_ACTIVATE چیدمان فعال عوض نمی‌شود
+fn load_hkl(klid: &str) -> Option<usize> {
+    let wide = U16CString::from_str(klid).ok()?;
+    let hkl = unsafe { LoadKeyboardLayoutW(wide.as_ptr(), KLF_NOTELLSHELL) };
+    (!hkl.is_null()).then_some(hkl as usize)
+}
+
+/// HKL فقط از LANGID حدس زده نمی‌شود: چیدمان‌های غیرپیش‌فرض (Dvorak، US-International، زبان en-US با
+/// کیبورد فارسی 0x04290409 و ...) همه LANGID یکسان دارند، پس HKL هر KLID از خود ویندوز گرفته می‌شود
+fn build_hkl_map(registry: &LayoutRegistry, mut load: impl FnMut(&str) -> Option<usize>) -> HklMap {
+    registry
+        .all()
+        .iter()
+        .filter_map(|l| Some((load(l.klid.as_deref()?)?, l.tag.clone())))
+        .collect()
+}
+
+/// فقط تطابق دقیق؛ HKL ناشناخته یعنی چیدمانی که در رجیستری نیست
+fn tag_for_hkl(map: &HklMap, hkl: usize) -> Option<String> {
+    map.iter().find(|(h, _)| *h == hkl).map(|(_, tag)| tag.clone())
+}
+
+impl LayoutBackend for WindowsBackend {
+    fn foreground_app(&mut self) -> Option<ForegroundApp> {
+        let pid = Self::foreground_pid()?;
+        let mut app = app_for_pid(&mut self.sys, pid)?;
+        let hwnd = unsafe { GetForegroundWindow() };
+        app.window = Some(hwnd as usize as u64);
+        app.title = Self::window_title(hwnd);
+        Some(app)
+    }
+
+    fn current_layout(&mut self, registry: &LayoutRegistry) -> Option<String> {
+        unsafe {
+            let hwnd = GetForegroundWindow();
+            if hwnd.is_null() {
+                return None;
+            }
+            let thread = GetWindowThreadProcessId(hwnd, std::ptr::null_mut());
+            let hkl = GetKeyboardLayout(thread);
+            self.hkl_to_tag(registry, hkl)
+        }
+    }
+
+    fn installed_layouts(&mut self, registry: &LayoutRegistry) -> Vec<String> {
+        unsafe {
+            let n = GetKeyboardLayoutList(0, std::ptr::null_mut());
+            if n <= 0 {
+                return Vec::new();
+            }
+            let mut list: Vec<HKL> = vec![std::ptr::null_mut(); n as usize];
+            let n = GetKeyboardLayoutList(n, list.as_mut_ptr());
+            list.truncate(n.max(0) as usize);
+            list.into_iter().filter_map(|hkl| self.hkl_to_tag(registry, hkl)).collect()
+        }
+    }
+
+    fn wait_for_focus_change(&mut self, timeout: Duration) -> bool {
+        if !self.ensure_hook() {
+            thread::sleep(timeout);
+            return true;
+        }
+        let deadline = Instant::now() + timeout;
+        loop {
+            Self::pump_messages();
+            if FOCUS_CHANGED.with(|f| f.replace(false)) {
+                return true;
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return false;
+            }
+            unsafe {
+                MsgWaitForMultipleObjects(0, std::ptr::null(), FALSE, left.as_millis() as DWORD, QS_ALLINPUT);
+            }
+        }
+    }
+
+    fn window_alive(&mut self, window: u64) -> bool {
+        unsafe { IsWindow(window as usize as HWND) != 0 }
+    }
+
+    fn set_layout(&mut self, layout: &Layout) -> bool {
+        let Some(klid) = layout.klid.as_deref() else { return false };
+        let Ok(wide) = U16CString::from_str(klid) else { return false };
+        unsafe {
+            let hkl = LoadKeyboardLayoutW(wide.as_ptr(), 1); // KLF_ACTIVATE = 1
+            let hwnd = GetForegroundWindow();
+            if hwnd.is_null() {
+                return false;
+            }
+            PostMessageW(hwnd, WM_INPUTLANGCHANGEREQUEST, 0 as WPARAM, hkl as LPARAM);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// HKLهایی که ویندوز برای این KLIDها برمی‌گرداند
+    fn fake_load(klid: &str) -> Option<usize> {
+        match klid {
+            "00000409" => Some(0x0409_0409),
+            "00000429" => Some(0x0429_0429),
+            "00010409" => Some(0xF002_0409),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn hkls_map_to_tags_only_on_exact_match() {
+        let reg = LayoutRegistry::default();
+        let map = build_hkl_map(&reg, fake_load);
+        assert_eq!(tag_for_hkl(&map, 0x0409_0409).as_deref(), Some("en"));
+        assert_eq!(tag_for_hkl(&map, 0x0429_0429).as_deref(), Some("fa"));
+        assert_eq!(tag_for_hkl(&map, 0xF002_0409).as_deref(), Some("en-dvorak"));
+        // US-International و زبان en-US با کیبورد فارسی همان LANGID را دارند ولی Dvorak نیستند
+        assert_eq!(tag_for_hkl(&map, 0xF001_0409), None);
+        assert_eq!(tag_for_hkl(&map, 0x0429_0409), None);
+    }
+
+    #[test]
+    fn layouts_without_klid_or_hkl_are_skipped() {
+        let reg = LayoutRegistry::new(&[Layout {
+            tag: "ku".into(),
+            name: "Kurdish".into(),
+            klid: None,
+            xkb_layout: Some("iq".into()),
+            xkb_variant: Some("ku".into()),
+        }]);
+        let map = build_hkl_map(&reg, fake_load);
+        assert_eq!(map.len(), 3);
+        assert!(map.iter().all(|(_, tag)| tag != "ku"));
+    }
+}
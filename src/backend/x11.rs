@@ -0,0 +1,337 @@
+// src/backend/x11.rs
+// پنجرهٔ فعال و چیدمان کیبورد روی X11 (EWMH + XKB)
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xkb::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt as _, EventMask, ModMask, Window,
+};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use sysinfo::{System, SystemExt};
+
+use super::{app_for_pid, ForegroundApp, LayoutBackend};
+use crate::layouts::{Layout, LayoutRegistry};
+
+pub type X11Result<T> = Result<T, Box<dyn std::error::Error>>;
+
+pub struct X11Backend {
+    sys: System,
+    conn: Arc<RustConnection>,
+    /// PropertyNotify روی _NET_ACTIVE_WINDOW و عنوان پنجرهٔ فعال؛ None یعنی رویداد در دسترس نیست و poll می‌کنیم
+    focus_rx: Option<Receiver<()>>,
+    /// XkbStateNotify با تغییر گروه؛ با اولین layout_changes برداشته می‌شود
+    layout_rx: Option<Receiver<()>>,
+    root: Window,
+    net_active_window: Atom,
+    net_wm_pid: Atom,
+    net_wm_name: Atom,
+    utf8_string: Atom,
+    xkb_rules_names: Atom,
+}
+
+fn intern(conn: &RustConnection, name: &[u8]) -> X11Result<Atom> {
+    Ok(conn.intern_atom(false, name)?.reply()?.atom)
+}
+
+impl X11Backend {
+    /// به $DISPLAY وصل می‌شود و افزونهٔ XKB را فعال می‌کند
+    pub fn connect() -> X11Result<Self> {
+        let (conn, screen_num) = x11rb::connect(None)?;
+        let root = conn.setup().roots[screen_num].root;
+
+        let xkb_ver = conn.xkb_use_extension(1, 0)?.reply()?;
+        if !xkb_ver.supported {
+            return Err("XKB extension is not supported by the X server".into());
+        }
+
+        let net_active_window = intern(&conn, b"_NET_ACTIVE_WINDOW")?;
+        let net_wm_pid = intern(&conn, b"_NET_WM_PID")?;
+        let net_wm_name = intern(&conn, b"_NET_WM_NAME")?;
+        let utf8_string = intern(&conn, b"UTF8_STRING")?;
+        let xkb_rules_names = intern(&conn, b"_XKB_RULES_NAMES")?;
+
+        let conn = Arc::new(conn);
+        let (focus_rx, layout_rx) = match Self::subscribe(&conn, root, net_active_window, net_wm_name) {
+            Some((focus, layout)) => (Some(focus), layout),
+            None => (None, None),
+        };
+        Ok(Self {
+            sys: System::new(),
+            conn,
+            focus_rx,
+            layout_rx,
+            root,
+            net_active_window,
+            net_wm_pid,
+            net_wm_name,
+            utf8_string,
+            xkb_rules_names,
+        })
+    }
+
+    /// یک thread رویدادهای X را می‌خواند و تغییر _NET_ACTIVE_WINDOW یا عنوان پنجرهٔ فعال را خبر می‌دهد؛
+    /// اگر XKB اجازه دهد تغییر گروه (چیدمان) هم جدا خبر داده می‌شود
+    fn subscribe(
+        conn: &Arc<RustConnection>,
+        root: Window,
+        net_active_window: Atom,
+        net_wm_name: Atom,
+    ) -> Option<(Receiver<()>, Option<Receiver<()>>)> {
+        if let Err(e) = watch_properties(conn, root) {
+            eprintln!("X11 focus events unavailable, polling instead: {}", e);
+            return None;
+        }
+        let layout_events = match watch_group(conn) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("XKB state events unavailable, polling layouts instead: {}", e);
+                false
+            }
+        };
+
+        let (tx, rx) = mpsc::channel();
+        let (layout_tx, layout_rx) = mpsc::channel();
+        let conn = Arc::clone(conn);
+        thread::spawn(move || {
+            let mut active = active_window(&conn, root, net_active_window);
+            if let Some(w) = active {
+                let _ = watch_properties(&conn, w);
+            }
+            while let Ok(ev) = conn.wait_for_event() {
+                if let Event::XkbStateNotify(e) = &ev {
+                    // اگر کسی مشترک چیدمان نیست خطای send مهم نیست
+                    if e.changed.contains(xkb::StatePart::GROUP_STATE) {
+                        let _ = layout_tx.send(());
+                    }
+                    continue;
+                }
+                let Event::PropertyNotify(e) = ev else { continue };
+                let notify = if e.window == root && e.atom == net_active_window {
+                    // پنجرهٔ فعال جدید را هم زیر نظر می‌گیریم تا تغییر عنوانش (تب مرورگر) دیده شود
+                    active = active_window(&conn, root, net_active_window);
+                    if let Some(w) = active {
+                        let _ = watch_properties(&conn, w);
+                    }
+                    true
+                } else {
+                    Some(e.window) == active && (e.atom == net_wm_name || e.atom == u32::from(AtomEnum::WM_NAME))
+                };
+                if notify && tx.send(()).is_err() {
+                    break;
+                }
+            }
+        });
+        Some((rx, layout_events.then_some(layout_rx)))
+    }
+
+    pub fn active_window(&self) -> Option<Window> {
+        active_window(&self.conn, self.root, self.net_active_window)
+    }
+
+    /// اول _NET_WM_NAME (UTF-8)، بعد WM_NAME قدیمی
+    pub fn window_title(&self, window: Window) -> Option<String> {
+        let read = |atom: Atom, ty: Atom| {
+            let reply = self.conn.get_property(false, window, atom, ty, 0, 1024).ok()?.reply().ok()?;
+            (!reply.value.is_empty()).then(|| String::from_utf8_lossy(&reply.value).into_owned())
+        };
+        read(self.net_wm_name, self.utf8_string)
+            .or_else(|| read(AtomEnum::WM_NAME.into(), AtomEnum::STRING.into()))
+    }
+
+    pub fn window_pid(&self, window: Window) -> Option<u32> {
+        let reply = self.conn
+            .get_property(false, window, self.net_wm_pid, AtomEnum::CARDINAL, 0, 1)
+            .ok()?
+            .reply()
+            .ok()?;
+        let pid = reply.value32()?.next()?;
+        (pid != 0).then_some(pid)
+    }
+
+    pub fn foreground_pid(&self) -> Option<u32> {
+        self.window_pid(self.active_window()?)
+    }
+
+    /// (layout, variant) برای هر گروه XKB، به ترتیب گروه‌ها
+    pub fn groups(&self) -> Vec<(String, Option<String>)> {
+        self.conn
+            .get_property(false, self.root, self.xkb_rules_names, AtomEnum::STRING, 0, 1024)
+            .ok()
+            .and_then(|c| c.reply().ok())
+            .map(|r| parse_rules_names(&r.value))
+            .unwrap_or_default()
+    }
+
+    pub fn current_group(&self) -> Option<u8> {
+        let state = self.conn.xkb_get_state(xkb::ID::USE_CORE_KBD.into()).ok()?.reply().ok()?;
+        Some(u8::from(state.locked_group))
+    }
+
+    pub fn lock_group(&self, group: u8) -> bool {
+        let sent = self.conn.xkb_latch_lock_state(
+            xkb::ID::USE_CORE_KBD.into(),
+            ModMask::from(0u16),
+            ModMask::from(0u16),
+            true,
+            xkb::Group::from(group),
+            ModMask::from(0u16),
+            false,
+            0,
+        );
+        sent.is_ok() && self.conn.flush().is_ok()
+    }
+}
+
+impl LayoutBackend for X11Backend {
+    fn foreground_app(&mut self) -> Option<ForegroundApp> {
+        let window = self.active_window()?;
+        let pid = self.window_pid(window)?;
+        let mut app = app_for_pid(&mut self.sys, pid)?;
+        app.window = Some(u64::from(window));
+        app.title = self.window_title(window);
+        Some(app)
+    }
+
+    fn current_layout(&mut self, registry: &LayoutRegistry) -> Option<String> {
+        let groups = self.groups();
+        let (layout, variant) = groups.get(self.current_group()? as usize)?;
+        registry.by_xkb(layout, variant.as_deref()).map(|l| l.tag.clone())
+    }
+
+    fn installed_layouts(&mut self, registry: &LayoutRegistry) -> Vec<String> {
+        self.groups()
+            .iter()
+            .filter_map(|(l, v)| registry.by_xkb(l, v.as_deref()).map(|x| x.tag.clone()))
+            .collect()
+    }
+
+    fn wait_for_focus_change(&mut self, timeout: Duration) -> bool {
+        let Some(rx) = &self.focus_rx else {
+            thread::sleep(timeout);
+            return true;
+        };
+        match rx.recv_timeout(timeout) {
+            Ok(()) => {
+                while rx.try_recv().is_ok() {}
+                true
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => {
+                eprintln!("X11 event thread stopped, falling back to polling");
+                self.focus_rx = None;
+                true
+            }
+        }
+    }
+
+    fn layout_changes(&mut self) -> Option<Receiver<()>> {
+        self.layout_rx.take()
+    }
+
+    fn window_alive(&mut self, window: u64) -> bool {
+        let Ok(window) = Window::try_from(window) else { return false };
+        self.conn
+            .get_window_attributes(window)
+            .ok()
+            .and_then(|c| c.reply().ok())
+            .is_some()
+    }
+
+    /// فقط گروه XKB عوض می‌شود؛ چیدمانی که جزو گروه‌ها نیست بارگذاری نمی‌شود،
+    /// چون setxkbmap لیست چیدمان‌ها و optionهای کاربر (مثل کلید toggle) را پاک می‌کند
+    fn set_layout(&mut self, layout: &Layout) -> bool {
+        let Some(xkb_layout) = layout.xkb_layout.as_deref() else { return false };
+        let variant = layout.xkb_variant.as_deref();
+
+        let groups = self.groups();
+        match groups.iter().position(|(l, v)| l == xkb_layout && v.as_deref() == variant) {
+            Some(idx) => self.lock_group(idx as u8),
+            None => {
+                eprintln!("Layout {} is not configured in XKB", layout.name);
+                false
+            }
+        }
+    }
+}
+
+fn active_window(conn: &RustConnection, root: Window, net_active_window: Atom) -> Option<Window> {
+    let reply = conn
+        .get_property(false, root, net_active_window, AtomEnum::WINDOW, 0, 1)
+        .ok()?
+        .reply()
+        .ok()?;
+    let window = reply.value32()?.next()?;
+    (window != 0).then_some(window)
+}
+
+/// فقط تغییر گروه قفل‌شده/مؤثر از میان رویدادهای state
+fn watch_group(conn: &RustConnection) -> Result<(), String> {
+    let details = xkb::SelectEventsAux::new().state_notify(xkb::SelectEventsAuxStateNotify {
+        affect_state: xkb::StatePart::GROUP_STATE,
+        state_details: xkb::StatePart::GROUP_STATE,
+    });
+    conn.xkb_select_events(
+        xkb::ID::USE_CORE_KBD.into(),
+        xkb::EventType::from(0u16),
+        xkb::EventType::from(0u16),
+        xkb::MapPart::from(0u16),
+        xkb::MapPart::from(0u16),
+        &details,
+    )
+    .map_err(|e| e.to_string())
+    .and_then(|c| c.check().map_err(|e| e.to_string()))
+}
+
+fn watch_properties(conn: &RustConnection, window: Window) -> Result<(), String> {
+    let aux = ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE);
+    conn.change_window_attributes(window, &aux)
+        .map_err(|e| e.to_string())
+        .and_then(|c| c.check().map_err(|e| e.to_string()))
+}
+
+/// _XKB_RULES_NAMES = "rules\0model\0layouts\0variants\0options\0"
+fn parse_rules_names(raw: &[u8]) -> Vec<(String, Option<String>)> {
+    let text = String::from_utf8_lossy(raw);
+    let mut parts = text.split('\0');
+    let layouts = parts.nth(2).unwrap_or("");
+    let variants: Vec<&str> = parts.next().unwrap_or("").split(',').collect();
+
+    layouts
+        .split(',')
+        .enumerate()
+        .filter(|(_, l)| !l.is_empty())
+        .map(|(i, l)| {
+            let v = variants.get(i).copied().filter(|v| !v.is_empty()).map(str::to_string);
+            (l.to_string(), v)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_names_are_split_per_group() {
+        let raw = b"evdev\0pc105\0us,ir,us\0,,dvorak\0grp:alt_shift_toggle\0";
+        assert_eq!(
+            parse_rules_names(raw),
+            vec![
+                ("us".to_string(), None),
+                ("ir".to_string(), None),
+                ("us".to_string(), Some("dvorak".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn rules_names_without_variants() {
+        assert_eq!(parse_rules_names(b"evdev\0pc105\0us\0\0\0"), vec![("us".to_string(), None)]);
+        assert!(parse_rules_names(b"").is_empty());
+    }
+}
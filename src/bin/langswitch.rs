@@ -0,0 +1,22 @@
+// src/bin/langswitch.rs
+// رابط خط فرمان برای اسکریپت‌نویسی؛ همهٔ منطق در cli.rs است
+use std::io;
+use std::process;
+
+use lang_switcher_rust::cli::{run, CliError, USAGE};
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let code = match run(&args, &mut io::stdout().lock()) {
+        Ok(code) => code,
+        Err(CliError::Usage(msg)) => {
+            eprintln!("langswitch: {}\n\n{}", msg, USAGE);
+            2
+        }
+        Err(CliError::Failed(msg)) => {
+            eprintln!("langswitch: {}", msg);
+            1
+        }
+    };
+    process::exit(code);
+}
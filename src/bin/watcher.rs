@@ -1,165 +1,93 @@
 // src/bin/watcher.rs
 #![cfg_attr(target_os = "windows", windows_subsystem = "windows")]
 // src/bin/watcher.rs
-use std::collections::HashMap;
-use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 use std::process::Command;
 use std::sync::{
-    atomic::{AtomicBool, Ordering},
+    atomic::Ordering,
     mpsc::{self, Receiver, Sender},
-    Arc, Mutex,
+    Arc,
 };
-use std::thread::{self, JoinHandle};
+use std::thread;
 use std::time::Duration;
 
-use image::{GenericImageView}; // لازم برای dimensions()
-use sysinfo::{ProcessExt, PidExt, System, SystemExt};
+use image::GenericImageView; // لازم برای dimensions()
 
 use tray_icon::{Icon, TrayIconBuilder};
 use tray_icon::menu::{Menu, MenuItem, MenuEvent};
 
-use winapi::shared::minwindef::UINT; // فقط ویندوز برای PeekMessage
-
-const CONFIG_FILE: &str = "lang_config.json";
-const POLL_MS: u64 = 300;
-// src/bin/watcher.rs
+use lang_switcher_rust::config::get_config_path;
+use lang_switcher_rust::instance;
+use lang_switcher_rust::ipc;
+use lang_switcher_rust::watch::{watch_loop, Control};
+#[cfg(target_os = "windows")]
+use lang_switcher_rust::backend::windows::WindowsBackend;
+#[cfg(target_os = "linux")]
+use lang_switcher_rust::backend::{gnome::GnomeBackend, hyprland::HyprlandBackend, kde::KdeBackend, sway::SwayBackend, x11::X11Backend, LayoutBackend};
 
+// ------------------ PLATFORM SPECIFIC ---------------------
+/// backend مناسب این پلتفرم را می‌سازد و حلقهٔ watcher را اجرا می‌کند.
+/// false یعنی backend بالا نیامد؛ آن وقت کل پروسه هم تمام می‌شود تا قفل و IPC بیهوده نماند
+fn run_watch_loop(control: Arc<Control>) -> bool {
+    #[cfg(target_os = "windows")]
+    {
+        println!("Windows watcher started.");
+        let mut backend = WindowsBackend::new();
+        watch_loop(&mut backend, &control);
+        println!("Windows watcher exiting.");
+        true
+    }
 
-fn get_config_path() -> PathBuf {
-    let mut dir = dirs::config_dir().unwrap_or_else(|| std::env::temp_dir());
-    dir.push("LangSwitcher");
-    fs::create_dir_all(&dir).ok();
-    let cfg_path = dir.join("lang_config.json");
-    if !cfg_path.exists() {
-        if let Ok(exe) = std::env::current_exe() {
-            if let Some(parent) = exe.parent() {
-                let default = parent.join("assets").join("lang_config.json");
-                if default.exists() { let _ = fs::copy(default, &cfg_path); }
+    #[cfg(target_os = "linux")]
+    {
+        let (kind, backend) = linux_backend();
+        let mut backend = match backend {
+            Ok(b) => b,
+            Err(e) => {
+                eprintln!("{} watcher could not start: {}", kind, e);
+                control.running.store(false, Ordering::SeqCst);
+                return false;
             }
-        }
+        };
+        println!("{} watcher started.", kind);
+        watch_loop(backend.as_mut(), &control);
+        println!("{} watcher exiting.", kind);
+        true
     }
-    cfg_path
-}
 
-fn load_config() -> HashMap<String, String> {
-    let cfg_path = get_config_path();
-    if !cfg_path.exists() { return HashMap::new(); }
-    match fs::read_to_string(&cfg_path) {
-        Ok(txt) => serde_json::from_str(&txt).unwrap_or_default(),
-        Err(_) => HashMap::new()
+    #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+    {
+        eprintln!("No watcher backend for this platform.");
+        control.running.store(false, Ordering::SeqCst);
+        false
     }
 }
 
-// ... بقیه کد watcher بدون تغییر، فقط CONFIG_FILE را با get_config_path() جایگزین کن
-
-
-
-// ------------------ PLATFORM SPECIFIC ---------------------
-#[cfg(target_os = "windows")]
-mod platform {
-    use super::*;
-    use widestring::U16CString;
-    use winapi::shared::minwindef::{DWORD, LPARAM, WPARAM};
-    use winapi::shared::windef::HWND;
-    use winapi::um::winuser::{
-        GetForegroundWindow, GetWindowThreadProcessId, LoadKeyboardLayoutW, PostMessageW,
-        WM_INPUTLANGCHANGEREQUEST,
-    };
-
-    fn get_foreground_pid() -> Option<u32> {
-        unsafe {
-            let hwnd: HWND = GetForegroundWindow();
-            if hwnd.is_null() {
-                return None;
-            }
-            let mut pid: DWORD = 0;
-            GetWindowThreadProcessId(hwnd, &mut pid as *mut u32);
-            if pid == 0 {
-                None
-            } else {
-                Some(pid as u32)
-            }
-        }
+/// زیر Sway و Hyprland سوکت‌های خودشان و زیر GNOME و KDE سرویس‌های D-Bus؛ X11 در Wayland فقط پنجره‌های Xwayland را می‌بیند
+#[cfg(target_os = "linux")]
+fn linux_backend() -> (&'static str, Result<Box<dyn LayoutBackend>, String>) {
+    if std::env::var_os("SWAYSOCK").is_some() {
+        return ("Sway", SwayBackend::connect().map(|b| Box::new(b) as Box<dyn LayoutBackend>).map_err(|e| e.to_string()));
     }
-
-    fn lang_to_klid(lang: &str) -> Option<&'static str> {
-        match lang {
-            "en" => Some("00000409"), // English (US)
-            "fa" => Some("00000429"), // Persian
-            _ => None,
-        }
+    if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        return ("Hyprland", HyprlandBackend::connect().map(|b| Box::new(b) as Box<dyn LayoutBackend>).map_err(|e| e.to_string()));
     }
-
-    pub fn set_layout_for_pid(_pid: u32, lang: &str) -> bool {
-        if let Some(klid) = lang_to_klid(lang) {
-            unsafe {
-                let wide = U16CString::from_str(klid).unwrap();
-                let hkl = LoadKeyboardLayoutW(wide.as_ptr(), 1); // KLF_ACTIVATE = 1
-                let hwnd = GetForegroundWindow();
-                if hwnd.is_null() {
-                    return false;
-                }
-                PostMessageW(hwnd, WM_INPUTLANGCHANGEREQUEST, 0 as WPARAM, hkl as LPARAM);
-                return true;
-            }
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+    if desktop.split(':').any(|d| d.eq_ignore_ascii_case("GNOME")) {
+        match GnomeBackend::connect() {
+            Ok(b) => return ("GNOME", Ok(Box::new(b))),
+            Err(e) => eprintln!("GNOME backend unavailable (is the Window Calls extension enabled?): {}", e),
         }
-        false
     }
-
-    pub fn watch_loop(running: Arc<AtomicBool>) {
-        println!("Windows watcher started.");
-        let mut sys = System::new_all();
-        let mut last_pid: Option<u32> = None;
-        let mut last_req_lang: Option<String> = None;
-
-        while running.load(Ordering::SeqCst) {
-            let cfg = super::load_config();
-
-            if let Some(pid) = get_foreground_pid() {
-                if Some(pid) != last_pid {
-                    sys.refresh_processes();
-                    let mut proc_name_opt: Option<String> = None;
-                    for proc in sys.processes().values() {
-                        if proc.pid().as_u32() == pid {
-                            proc_name_opt = Some(proc.name().to_string());
-                            break;
-                        }
-                    }
-                    if let Some(proc_name) = proc_name_opt {
-                        let mut desired: Option<String> = None;
-                        if let Some(v) = cfg.get(&proc_name) {
-                            desired = Some(v.clone());
-                        } else {
-                            for (k, v) in cfg.iter() {
-                                if k.eq_ignore_ascii_case(&proc_name) {
-                                    desired = Some(v.clone());
-                                    break;
-                                }
-                            }
-                        }
-                        if let Some(lang) = desired {
-                            if last_req_lang.as_ref().map(|s| s.as_str()) != Some(lang.as_str()) {
-                                if set_layout_for_pid(pid, &lang) {
-                                    println!("Requested layout {} for {}", lang, proc_name);
-                                    last_req_lang = Some(lang);
-                                }
-                            }
-                        } else {
-                            last_req_lang = None;
-                        }
-                    }
-                    last_pid = Some(pid);
-                }
-            }
-            thread::sleep(Duration::from_millis(super::POLL_MS));
+    if desktop.split(':').any(|d| d.eq_ignore_ascii_case("KDE")) {
+        match KdeBackend::connect() {
+            Ok(b) => return ("KDE", Ok(Box::new(b))),
+            Err(e) => eprintln!("KDE backend unavailable: {}", e),
         }
-        println!("Windows watcher exiting.");
     }
+    ("X11", X11Backend::connect().map(|b| Box::new(b) as Box<dyn LayoutBackend>).map_err(|e| e.to_string()))
 }
 
-// (Linux کد مشابه داره — همونی که قبلاً داشتی، حذف نکردم)
-
 // ---------------------- Tray Commands ---------------------
 #[derive(Debug)]
 enum MenuCommand {
@@ -168,45 +96,71 @@ enum MenuCommand {
     Quit,
 }
 
-fn is_gui_running() -> bool {
-    let targets = ["lang_switcher_rust.exe", "lang_switcher_rust"];
-    let mut sys = System::new_all();
-    sys.refresh_processes();
-    for (_pid, p) in sys.processes() {
-        let name = p.name();
-        for t in &targets {
-            if name.eq_ignore_ascii_case(t) {
-                return true;
-            }
+/// اگر GUI باز است جلو بیاوردش، وگرنه اجرایش کن
+fn open_settings() {
+    if ipc::call_at(&ipc::endpoint_for(ipc::GUI), ipc::Command::Activate { args: Vec::new() }).is_ok() {
+        return;
+    }
+    if let Ok(exe_path) = std::env::current_exe() {
+        if let Some(parent) = exe_path.parent() {
+            let name = if cfg!(windows) { "lang_switcher_rust.exe" } else { "lang_switcher_rust" };
+            let _ = Command::new(parent.join(name)).spawn();
         }
     }
-    false
 }
 
 // -------------------------- MAIN --------------------------
 fn main() {
-    println!("Layout watcher. Config: {}", CONFIG_FILE);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let _instance = match instance::acquire("watcher") {
+        Ok(Some(lock)) => Some(lock),
+        Ok(None) => {
+            // watcher دیگری اجراست: دو حلقه سر چیدمان دعوا نکنند، آرگومان‌ها را به آن بده و برو
+            match ipc::call(ipc::Command::Activate { args }) {
+                Ok(_) => println!("Watcher already running; handed over to it."),
+                Err(e) => eprintln!("Watcher already running but not reachable: {}", e),
+            }
+            return;
+        }
+        Err(e) => {
+            eprintln!("Could not take the single-instance lock: {}", e);
+            None
+        }
+    };
+    println!("Layout watcher. Config: {}", get_config_path().display());
 
-    let running = Arc::new(AtomicBool::new(true));
-    let watcher_handle: Arc<Mutex<Option<JoinHandle<()>>>> = Arc::new(Mutex::new(None));
+    let control = Arc::new(Control::new());
+    let (tx, rx): (Sender<MenuCommand>, Receiver<MenuCommand>) = mpsc::channel();
+    if args.iter().any(|a| a == "--settings") {
+        let _ = tx.send(MenuCommand::Settings);
+    }
 
-    // spawn watcher
-    let spawn_watcher = {
-        let running = Arc::clone(&running);
-        let handle = Arc::clone(&watcher_handle);
-        move || {
-            running.store(true, Ordering::SeqCst);
-            let r = Arc::clone(&running);
-            let joinh = thread::spawn(move || {
-                #[cfg(target_os = "windows")]
-                crate::platform::watch_loop(r);
-                #[cfg(target_os = "linux")]
-                crate::platform::watch_loop(r);
-            });
-            *handle.lock().unwrap() = Some(joinh);
+    // GUI و CLI از این راه وضعیت می‌گیرند و فرمان می‌دهند
+    let handler = {
+        let control = Arc::clone(&control);
+        let tx = tx.clone();
+        Arc::new(move |cmd| match cmd {
+            ipc::Command::Activate { args } => {
+                if args.iter().any(|a| a == "--settings") {
+                    let _ = tx.send(MenuCommand::Settings);
+                }
+                ipc::Response::ok()
+            }
+            cmd => control.handle(cmd),
+        })
+    };
+    let server = match ipc::Server::bind(handler) {
+        Ok(s) => Some(s),
+        Err(e) => {
+            eprintln!("IPC endpoint {} unavailable: {}", ipc::endpoint().display(), e);
+            None
         }
     };
-    spawn_watcher();
+
+    let watcher_handle = {
+        let control = Arc::clone(&control);
+        thread::spawn(move || run_watch_loop(control))
+    };
 
     // load icon
     let mut icon_path = PathBuf::from("assets/icon.ico");
@@ -223,16 +177,21 @@ fn main() {
     let (w, h) = dynimg.dimensions();
     let icon_bytes = rgba.into_raw();
 
-    let (tx, rx): (Sender<MenuCommand>, Receiver<MenuCommand>) = mpsc::channel();
-
     // tray thread
     {
         let tx = tx.clone();
         let icon_bytes_clone = icon_bytes.clone();
         thread::spawn(move || {
+            // tray-icon روی لینوکس باید روی thread حلقهٔ gtk ساخته شود
+            #[cfg(target_os = "linux")]
+            if let Err(e) = gtk::init() {
+                eprintln!("gtk init failed, no tray icon: {}", e);
+                return;
+            }
+
             let icon = Icon::from_rgba(icon_bytes_clone, w, h).unwrap();
 
-            let mut menu = Menu::new();
+            let menu = Menu::new();
             let toggle_item = MenuItem::new("Toggle watcher", true, None);
             let settings_item = MenuItem::new("Settings", true, None);
             let quit_item = MenuItem::new("Quit", true, None);
@@ -262,11 +221,11 @@ fn main() {
                         }
                     }
                     while let Ok(ev) = menu_rx.try_recv() {
-                        if ev.id() == &toggle_item.id() {
+                        if ev.id() == toggle_item.id() {
                             let _ = tx.send(MenuCommand::Toggle);
-                        } else if ev.id() == &settings_item.id() {
+                        } else if ev.id() == settings_item.id() {
                             let _ = tx.send(MenuCommand::Settings);
-                        } else if ev.id() == &quit_item.id() {
+                        } else if ev.id() == quit_item.id() {
                             let _ = tx.send(MenuCommand::Quit);
                         }
                     }
@@ -274,14 +233,31 @@ fn main() {
                 }
             }
 
-            #[cfg(not(target_os = "windows"))]
+            #[cfg(target_os = "linux")]
+            {
+                gtk::glib::timeout_add_local(Duration::from_millis(50), move || {
+                    while let Ok(ev) = menu_rx.try_recv() {
+                        if ev.id() == toggle_item.id() {
+                            let _ = tx.send(MenuCommand::Toggle);
+                        } else if ev.id() == settings_item.id() {
+                            let _ = tx.send(MenuCommand::Settings);
+                        } else if ev.id() == quit_item.id() {
+                            let _ = tx.send(MenuCommand::Quit);
+                        }
+                    }
+                    gtk::glib::ControlFlow::Continue
+                });
+                gtk::main();
+            }
+
+            #[cfg(not(any(target_os = "windows", target_os = "linux")))]
             {
                 while let Ok(ev) = menu_rx.recv() {
-                    if ev.id() == &toggle_item.id() {
+                    if ev.id() == toggle_item.id() {
                         let _ = tx.send(MenuCommand::Toggle);
-                    } else if ev.id() == &settings_item.id() {
+                    } else if ev.id() == settings_item.id() {
                         let _ = tx.send(MenuCommand::Settings);
-                    } else if ev.id() == &quit_item.id() {
+                    } else if ev.id() == quit_item.id() {
                         let _ = tx.send(MenuCommand::Quit);
                     }
                 }
@@ -290,38 +266,23 @@ fn main() {
     }
 
     // main loop
-    loop {
+    while control.running.load(Ordering::SeqCst) {
         if let Ok(cmd) = rx.try_recv() {
             match cmd {
                 MenuCommand::Toggle => {
-                    if running.load(Ordering::SeqCst) {
-                        running.store(false, Ordering::SeqCst);
-                        if let Some(h) = watcher_handle.lock().unwrap().take() {
-                            let _ = h.join();
-                        }
-                    } else {
-                        spawn_watcher();
-                    }
-                }
-                MenuCommand::Settings => {
-                    if !is_gui_running() {
-                        if let Ok(exe_path) = std::env::current_exe() {
-                            if let Some(parent) = exe_path.parent() {
-                                let gui = parent.join("lang_switcher_rust.exe");
-                                let _ = Command::new(gui).spawn();
-                            }
-                        }
-                    }
+                    let paused = control.paused.load(Ordering::SeqCst);
+                    control.set_paused(!paused);
                 }
+                MenuCommand::Settings => open_settings(),
                 MenuCommand::Quit => {
-                    running.store(false, Ordering::SeqCst);
-                    if let Some(h) = watcher_handle.lock().unwrap().take() {
-                        let _ = h.join();
-                    }
-                    std::process::exit(0);
+                    control.running.store(false, Ordering::SeqCst);
                 }
             }
         }
         thread::sleep(Duration::from_millis(150));
     }
+    // Quit از تری، shutdown از IPC یا backend که بالا نیامد
+    let started = watcher_handle.join().unwrap_or(false);
+    drop(server); // exit مخرب‌ها را اجرا نمی‌کند؛ سوکت باید پاک شود
+    std::process::exit(if started { 0 } else { 1 });
 }
@@ -0,0 +1,387 @@
+// src/cli.rs
+// فرمان‌های `langswitch`؛ خود باینری فقط آرگومان‌ها را به run می‌دهد تا اینجا قابل تست باشد
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::config::{get_config_path, Config, ConfigError};
+use crate::core::LangState;
+use crate::ipc;
+use crate::rules::{ProcessFacts, RuleSet};
+
+pub const USAGE: &str = "\
+usage: langswitch [--json] [--config FILE] <command>
+
+commands:
+  list                   running programs and their rules
+  get <exe>              layout for a program (exit code 1 if none)
+  set <exe> <lang>       add or change the rule for a program
+  unset <exe>            remove the rule for a program
+  export [FILE]          write the config as JSON (stdout if no FILE)
+  import <FILE|-> [--replace]
+                         merge rules from a config file (or replace them all)
+  status                 whether the watcher is running and its settings
+  pause | resume         stop or restart switching without quitting the watcher
+  reload                 make the watcher re-read the config now
+  apply                  re-apply the rule for the focused window
+  shutdown               quit the watcher";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    List,
+    Get { exe: String },
+    Set { exe: String, lang: String },
+    Unset { exe: String },
+    Export { file: Option<PathBuf> },
+    Import { file: String, replace: bool },
+    Status,
+    /// فرمان مستقیم به watcher از طریق IPC
+    Watcher(ipc::Command),
+    Help,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cli {
+    pub format: Format,
+    pub config: Option<PathBuf>,
+    pub command: Command,
+}
+
+#[derive(Debug)]
+pub enum CliError {
+    /// آرگومان‌های غلط؛ باینری متن usage را هم چاپ می‌کند
+    Usage(String),
+    Failed(String),
+}
+
+impl From<ConfigError> for CliError {
+    fn from(e: ConfigError) -> Self {
+        CliError::Failed(e.to_string())
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(e: io::Error) -> Self {
+        CliError::Failed(e.to_string())
+    }
+}
+
+pub fn parse_args(args: &[String]) -> Result<Cli, CliError> {
+    let mut format = Format::Text;
+    let mut config = None;
+    let mut replace = false;
+    let mut words: Vec<&str> = Vec::new();
+
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+        match a.as_str() {
+            "--json" => format = Format::Json,
+            "--replace" => replace = true,
+            "--config" => {
+                let p = it.next().ok_or_else(|| CliError::Usage("--config needs a file".into()))?;
+                config = Some(PathBuf::from(p));
+            }
+            "-h" | "--help" => words.insert(0, "help"),
+            s if s.starts_with("--") => return Err(CliError::Usage(format!("unknown option {}", s))),
+            s => words.push(s),
+        }
+    }
+
+    let command = match words.as_slice() {
+        [] | ["help", ..] => Command::Help,
+        ["list"] => Command::List,
+        ["status"] => Command::Status,
+        ["pause"] => Command::Watcher(ipc::Command::Pause),
+        ["resume"] => Command::Watcher(ipc::Command::Resume),
+        ["reload"] => Command::Watcher(ipc::Command::Reload),
+        ["apply"] => Command::Watcher(ipc::Command::ApplyNow),
+        ["shutdown"] => Command::Watcher(ipc::Command::Shutdown),
+        ["get", exe] => Command::Get { exe: exe.to_string() },
+        ["set", exe, lang] => Command::Set { exe: exe.to_string(), lang: lang.to_string() },
+        ["unset", exe] => Command::Unset { exe: exe.to_string() },
+        ["export"] => Command::Export { file: None },
+        ["export", file] => Command::Export { file: Some(PathBuf::from(file)) },
+        ["import", file] => Command::Import { file: file.to_string(), replace },
+        [cmd, ..] => return Err(CliError::Usage(format!("bad arguments for '{}'", cmd))),
+    };
+    if replace && !matches!(command, Command::Import { .. }) {
+        return Err(CliError::Usage("--replace only applies to import".into()));
+    }
+    Ok(Cli { format, config, command })
+}
+
+/// فایل نبود = کانفیگ خالی؛ ولی فایل خراب برخلاف GUI خطاست تا اسکریپت بفهمد
+fn load(path: &Path) -> Result<Config, CliError> {
+    match Config::try_load_from(path) {
+        Ok(cfg) => Ok(cfg),
+        Err(ConfigError::Io(e)) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(CliError::Failed(format!("{}: {}", path.display(), e))),
+    }
+}
+
+fn print(out: &mut dyn Write, format: Format, text: &str, value: Value) -> Result<(), CliError> {
+    match format {
+        Format::Text => writeln!(out, "{}", text)?,
+        Format::Json => writeln!(out, "{}", value)?,
+    }
+    Ok(())
+}
+
+/// کد خروج را برمی‌گرداند (0 موفق، 1 برای get بدون نتیجه)
+pub fn run(args: &[String], out: &mut dyn Write) -> Result<i32, CliError> {
+    let cli = parse_args(args)?;
+    let path = cli.config.clone().unwrap_or_else(get_config_path);
+    let format = cli.format;
+
+    match cli.command {
+        Command::Help => {
+            writeln!(out, "{}", USAGE)?;
+        }
+        Command::List => {
+            let state = LangState::from_config(load(&path)?);
+            match format {
+                Format::Text => {
+                    for p in &state.programs {
+                        let state = if p.running { "running" } else { "not running" };
+                        writeln!(out, "{:<32} {:<10} {}", p.name, p.lang.as_deref().unwrap_or("-"), state)?;
+                    }
+                }
+                Format::Json => {
+                    let list: Vec<Value> = state
+                        .programs
+                        .iter()
+                        .map(|p| json!({ "name": p.name, "lang": p.lang, "running": p.running, "exe_path": p.exe_path }))
+                        .collect();
+                    writeln!(out, "{}", Value::Array(list))?;
+                }
+            }
+        }
+        Command::Get { exe } => {
+            let cfg = load(&path)?;
+            let rules = RuleSet::compile(&cfg.rules).map_err(CliError::Failed)?;
+            let facts = ProcessFacts { name: &exe, ..ProcessFacts::default() };
+            let (lang, source) = match rules.lookup(&facts) {
+                Some(i) => (Some(cfg.rules[i].lang.as_str()), Some("rule")),
+                None => match cfg.learned_lang(&exe).filter(|_| cfg.settings.learning) {
+                    Some(l) => (Some(l), Some("learned")),
+                    None => (None, None),
+                },
+            };
+            match format {
+                Format::Text => {
+                    if let Some(l) = lang {
+                        writeln!(out, "{}", l)?;
+                    }
+                }
+                Format::Json => writeln!(out, "{}", json!({ "process": exe, "lang": lang, "source": source }))?,
+            }
+            if lang.is_none() {
+                return Ok(1);
+            }
+        }
+        Command::Set { exe, lang } => {
+            let tag = {
+                let registry = load(&path)?.registry();
+                let Some(layout) = registry.get(&lang) else {
+                    let known: Vec<&str> = registry.all().iter().map(|l| l.tag.as_str()).collect();
+                    return Err(CliError::Usage(format!("unknown layout '{}' (known: {})", lang, known.join(", "))));
+                };
+                layout.tag.clone()
+            };
+            Config::update_at(&path, |c| c.set_lang(&exe, &tag))?;
+            print(out, format, &format!("{} -> {}", exe, tag), json!({ "process": exe, "lang": tag }))?;
+        }
+        Command::Unset { exe } => {
+            let mut removed = false;
+            Config::update_at(&path, |c| removed = c.remove_rule(&exe))?;
+            let text = if removed { format!("removed rule for {}", exe) } else { format!("no rule for {}", exe) };
+            print(out, format, &text, json!({ "process": exe, "removed": removed }))?;
+        }
+        Command::Export { file } => {
+            let txt = serde_json::to_string_pretty(&load(&path)?).map_err(|e| CliError::Failed(e.to_string()))?;
+            match file {
+                Some(f) => fs::write(&f, txt + "\n")?,
+                None => writeln!(out, "{}", txt)?,
+            }
+        }
+        Command::Import { file, replace } => {
+            let txt = if file == "-" {
+                let mut s = String::new();
+                io::stdin().read_to_string(&mut s)?;
+                s
+            } else {
+                fs::read_to_string(&file)?
+            };
+            let incoming = Config::parse(&txt).map_err(|e| CliError::Failed(format!("{}: {}", file, e)))?;
+            let merge = |c: &mut Config| {
+                for l in &incoming.layouts {
+                    c.layouts.retain(|x| !x.tag.eq_ignore_ascii_case(&l.tag));
+                    c.layouts.push(l.clone());
+                }
+                if replace {
+                    c.rules = incoming.rules.clone();
+                } else {
+                    c.merge_rules(&incoming.rules);
+                }
+            };
+            // قبل از نوشتن، نتیجه را اعتبارسنجی می‌کنیم تا کانفیگ خراب روی دیسک نرود
+            let mut preview = load(&path)?;
+            merge(&mut preview);
+            preview.validate()?;
+            let cfg = Config::update_at(&path, merge)?;
+            let text = format!("imported {} rules ({} total)", incoming.rules.len(), cfg.rules.len());
+            print(out, format, &text, json!({ "imported": incoming.rules.len(), "total": cfg.rules.len() }))?;
+        }
+        Command::Status => {
+            let cfg = load(&path)?;
+            let live = ipc::call(ipc::Command::Status).ok().and_then(|r| r.status);
+            match format {
+                Format::Text => {
+                    match &live {
+                        None => writeln!(out, "watcher: stopped")?,
+                        Some(s) => {
+                            let state = if s.paused { "paused" } else { "running" };
+                            writeln!(out, "watcher: {} (pid {})", state, s.pid)?;
+                            if let Some(app) = &s.app {
+                                let layout = s.layout.as_deref().unwrap_or("?");
+                                let target = s.target.as_deref().unwrap_or("-");
+                                writeln!(out, "focused: {} (layout {}, wants {})", app, layout, target)?;
+                            }
+                            for e in &s.events {
+                                writeln!(out, "event: {}", e)?;
+                            }
+                        }
+                    }
+                    writeln!(out, "config: {} (generation {})", path.display(), cfg.generation)?;
+                    writeln!(out, "rules: {}, learned: {}", cfg.rules.len(), cfg.learned.len())?;
+                    writeln!(out, "unmapped: {} (default {})", cfg.defaults.unmapped.label(), cfg.defaults.lang)?;
+                    writeln!(out, "learning: {}, per window: {}", cfg.settings.learning, cfg.settings.per_window)?;
+                }
+                Format::Json => {
+                    let v = json!({
+                        "watcher": live,
+                        "config": {
+                            "path": path,
+                            "generation": cfg.generation,
+                            "rules": cfg.rules.len(),
+                            "learned": cfg.learned.len(),
+                            "unmapped": cfg.defaults.unmapped,
+                            "default_lang": cfg.defaults.lang,
+                            "learning": cfg.settings.learning,
+                            "per_window": cfg.settings.per_window,
+                        },
+                    });
+                    writeln!(out, "{}", v)?;
+                }
+            }
+        }
+        Command::Watcher(cmd) => {
+            let resp = ipc::call(cmd).map_err(|e| CliError::Failed(format!("watcher is not running ({})", e)))?;
+            if !resp.ok {
+                return Err(CliError::Failed(resp.error.unwrap_or_else(|| "watcher refused".into())));
+            }
+            print(out, format, "ok", json!({ "ok": true }))?;
+        }
+    }
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn temp_config(tag: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("langswitcher-cli-{}-{}", tag, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("lang_config.json")
+    }
+
+    fn run_on(path: &Path, cmd: &str) -> (i32, String) {
+        let mut all = vec!["--config".to_string(), path.display().to_string()];
+        all.extend(args(cmd));
+        let mut out = Vec::new();
+        let code = run(&all, &mut out).unwrap();
+        (code, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn parses_commands_and_flags() {
+        let cli = parse_args(&args("--json set chrome.exe fa")).unwrap();
+        assert_eq!(cli.format, Format::Json);
+        assert_eq!(cli.command, Command::Set { exe: "chrome.exe".into(), lang: "fa".into() });
+        assert_eq!(parse_args(&args("import x.json --replace")).unwrap().command, Command::Import {
+            file: "x.json".into(),
+            replace: true
+        });
+        assert_eq!(parse_args(&[]).unwrap().command, Command::Help);
+        assert_eq!(parse_args(&args("apply")).unwrap().command, Command::Watcher(ipc::Command::ApplyNow));
+        assert!(matches!(parse_args(&args("set chrome.exe")), Err(CliError::Usage(_))));
+        assert!(matches!(parse_args(&args("list --replace")), Err(CliError::Usage(_))));
+        assert!(matches!(parse_args(&args("--verbose list")), Err(CliError::Usage(_))));
+    }
+
+    #[test]
+    fn set_get_unset_round_trip() {
+        let path = temp_config("rw");
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(run_on(&path, "get chrome.exe"), (1, String::new()));
+        assert_eq!(run_on(&path, "set chrome.exe FA").1, "chrome.exe -> fa\n");
+        assert_eq!(run_on(&path, "get CHROME.EXE"), (0, "fa\n".into()));
+        assert_eq!(
+            run_on(&path, "--json get chrome.exe").1,
+            "{\"lang\":\"fa\",\"process\":\"chrome.exe\",\"source\":\"rule\"}\n"
+        );
+        assert!(matches!(run(&args("--config x set a.exe zz"), &mut Vec::new()), Err(CliError::Usage(_))));
+
+        assert_eq!(run_on(&path, "--json unset chrome.exe").1, "{\"process\":\"chrome.exe\",\"removed\":true}\n");
+        assert_eq!(run_on(&path, "unset chrome.exe").1, "no rule for chrome.exe\n");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn export_then_import_merges_or_replaces() {
+        let src = temp_config("export");
+        let dst = src.with_file_name("other.json");
+        let dump = src.with_file_name("dump.json");
+        for p in [&src, &dst, &dump] {
+            let _ = fs::remove_file(p);
+        }
+
+        run_on(&src, "set chrome.exe fa");
+        run_on(&src, "set Code.exe en");
+        run_on(&src, &format!("export {}", dump.display()));
+
+        run_on(&dst, "set chrome.exe en");
+        run_on(&dst, "set Telegram.exe fa");
+        let (_, out) = run_on(&dst, &format!("import {}", dump.display()));
+        assert_eq!(out, "imported 2 rules (3 total)\n");
+        assert_eq!(run_on(&dst, "get chrome.exe").1, "fa\n");
+
+        run_on(&dst, &format!("import {} --replace", dump.display()));
+        assert_eq!(run_on(&dst, "get Telegram.exe").0, 1);
+
+        fs::write(&dump, r#"{ "version": 1, "rules": [{ "process": "a.exe", "lang": "zz" }] }"#).unwrap();
+        let mut out = Vec::new();
+        let all = vec!["--config".into(), dst.display().to_string(), "import".into(), dump.display().to_string()];
+        assert!(matches!(run(&all, &mut out), Err(CliError::Failed(_))));
+        assert_eq!(run_on(&dst, "get Code.exe").1, "en\n");
+
+        for p in [&src, &dst, &dump] {
+            let _ = fs::remove_file(p);
+        }
+    }
+}
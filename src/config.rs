@@ -0,0 +1,857 @@
+// src/config.rs
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+use std::fmt;
+use std::fs::{self, TryLockError};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::layouts::{Layout, LayoutRegistry};
+use crate::rules::RuleSet;
+
+pub const CONFIG_FILE: &str = "lang_config.json";
+pub const CONFIG_VERSION: u32 = 1;
+
+/// مسیر فایل کانفیگ کاربر؛ اگر وجود نداشته باشد نسخه پیش‌فرض از assets کپی می‌شود
+pub fn get_config_path() -> PathBuf {
+    let mut dir = dirs::config_dir().unwrap_or_else(env::temp_dir);
+    dir.push("LangSwitcher");
+    fs::create_dir_all(&dir).ok();
+    let cfg_path = dir.join(CONFIG_FILE);
+
+    // نسخه پیش‌فرض را از assets کپی کن
+    if !cfg_path.exists() {
+        if let Ok(exe) = env::current_exe() {
+            if let Some(parent) = exe.parent() {
+                let default = parent.join("assets").join(CONFIG_FILE);
+                if default.exists() {
+                    let _ = fs::copy(default, &cfg_path);
+                }
+            }
+        }
+    }
+    cfg_path
+}
+
+/// یک قانون: شرط‌ها -> زبان. همهٔ شرط‌های داده‌شده باید برقرار باشند؛
+/// ترتیب اولویت بین قوانین در `rules::RuleSet` تعریف شده است.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Rule {
+    /// نام exe، بدون حساسیت به حروف ("chrome.exe")
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub process: String,
+    /// مسیر کامل exe
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exe_path: Option<String>,
+    /// الگوی glob روی مسیر کامل exe ("C:/Tools/**/python.exe")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_glob: Option<String>,
+    /// regex روی نام پروسه
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name_regex: Option<String>,
+    /// regex روی آرگومان‌های خط فرمان (با فاصله به هم چسبیده)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args_regex: Option<String>,
+    /// بخشی از عنوان پنجرهٔ فعال، بدون حساسیت به حروف ("GitHub")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// regex روی عنوان پنجرهٔ فعال
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title_regex: Option<String>,
+    pub lang: String,
+}
+
+impl Rule {
+    /// قانون ساده بر اساس نام پروسه (همان چیزی که GUI می‌سازد)
+    pub fn for_process(process: &str, lang: &str) -> Self {
+        Self { process: process.to_string(), lang: lang.to_string(), ..Self::default() }
+    }
+
+    /// فقط نام پروسه، بدون شرط دیگر
+    pub fn is_plain(&self) -> bool {
+        self.exe_path.is_none()
+            && self.path_glob.is_none()
+            && self.name_regex.is_none()
+            && self.args_regex.is_none()
+            && self.title.is_none()
+            && self.title_regex.is_none()
+    }
+}
+
+/// برنامه‌ای که هیچ قانونی به آن نمی‌خورد
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UnmappedPolicy {
+    /// چیدمان برنامهٔ قبلی می‌ماند (رفتار قدیمی)
+    #[default]
+    Leave,
+    /// `defaults.lang`
+    Default,
+    /// چیدمانی که پیش از اولین تعویض قانونی فعال بود
+    Restore,
+}
+
+impl UnmappedPolicy {
+    pub const ALL: [UnmappedPolicy; 3] = [UnmappedPolicy::Leave, UnmappedPolicy::Default, UnmappedPolicy::Restore];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            UnmappedPolicy::Leave => "leave",
+            UnmappedPolicy::Default => "default",
+            UnmappedPolicy::Restore => "restore",
+        }
+    }
+}
+
+/// مقادیر پیش‌فرض برای برنامه‌هایی که قانون ندارند
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Defaults {
+    /// چیدمان سراسری برای `unmapped: "default"`
+    pub lang: String,
+    pub unmapped: UnmappedPolicy,
+}
+
+impl Default for Defaults {
+    fn default() -> Self {
+        Self { lang: "en".to_string(), unmapped: UnmappedPolicy::Leave }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    /// فاصلهٔ polling وقتی backend رویداد فوکوس ندارد؛ در حالت رویدادی حداکثر زمان انتظار
+    pub poll_ms: u64,
+    /// یادگیری خودکار از تعویض دستی چیدمان؛ پیش‌فرض خاموش
+    pub learning: bool,
+    /// چیدمان دستی باید این مدت بماند تا یک «رأی» حساب شود
+    pub learn_debounce_ms: u64,
+    /// چند رأی پشت سر هم برای همان چیدمان تا در `learned` ثبت شود
+    pub learn_confidence: u32,
+    /// آخرین چیدمان هر پنجره جدا نگه داشته و با برگشت فوکوس به آن پنجره بازگردانده می‌شود
+    pub per_window: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self { poll_ms: 300, learning: false, learn_debounce_ms: 2000, learn_confidence: 3, per_window: false }
+    }
+}
+
+/// چیدمانی که watcher از تعویض‌های دستی کاربر یاد گرفته؛ جدا از `rules` تا کاربر آن را مرور و تأیید کند
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Learned {
+    pub process: String,
+    pub lang: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Config {
+    pub version: u32,
+    /// با هر ذخیره یکی زیاد می‌شود تا GUI و watcher تغییرات هم را بازنویسی نکنند
+    #[serde(default)]
+    pub generation: u64,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    #[serde(default)]
+    pub defaults: Defaults,
+    #[serde(default)]
+    pub settings: Settings,
+    /// فقط وقتی `settings.learning` روشن است استفاده می‌شود؛ قوانین صریح همیشه مقدم‌اند
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub learned: Vec<Learned>,
+    /// چیدمان‌های اضافه یا جایگزین برای جدول داخلی
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layouts: Vec<Layout>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            generation: 0,
+            rules: Vec::new(),
+            defaults: Defaults::default(),
+            settings: Settings::default(),
+            learned: Vec::new(),
+            layouts: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Self {
+        Self::load_from(&get_config_path())
+    }
+
+    /// فایل خراب یا ناموجود = کانفیگ خالی (خطا فقط لاگ می‌شود)
+    pub fn load_from(path: &Path) -> Self {
+        match Self::try_load_from(path) {
+            Ok(cfg) => cfg,
+            Err(ConfigError::Io(e)) if e.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                eprintln!("Config {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// خواندن + ارتقا + اعتبارسنجی.
+    /// فایل‌های قدیمی (نسخه 0) همان‌جا به نسخهٔ جدید ارتقا پیدا می‌کنند و یک بکاپ کنارشان می‌ماند.
+    /// نوشتن زیر قفل و فقط اگر فایل هنوز نسخه 0 باشد، تا ذخیرهٔ همزمان برنامهٔ دیگر رونویسی نشود.
+    pub fn try_load_from(path: &Path) -> Result<Self, ConfigError> {
+        let (cfg, v0) = Self::read(path)?;
+        if v0.is_none() {
+            return Ok(cfg);
+        }
+        let _lock = ConfigLock::acquire(path)?;
+        let (cfg, v0) = Self::read(path)?;
+        if let Some(txt) = v0 {
+            cfg.write_migrated(path, &txt);
+        }
+        Ok(cfg)
+    }
+
+    /// بدون نوشتن؛ مقدار دوم متن اصلی فایل است اگر نسخه 0 بود
+    fn read(path: &Path) -> Result<(Self, Option<String>), ConfigError> {
+        let txt = fs::read_to_string(path)?;
+        let (cfg, migrated) = migrate(&txt)?;
+        cfg.validate()?;
+        Ok((cfg, migrated.then_some(txt)))
+    }
+
+    /// فقط زیر ConfigLock صدا زده شود
+    fn write_migrated(&self, path: &Path, v0: &str) {
+        let backup = backup_path(path);
+        match fs::write(&backup, v0).and_then(|_| self.write_atomic(path)) {
+            Ok(()) => println!("Migrated config to v{} (backup: {})", self.version, backup.display()),
+            Err(e) => eprintln!("Config migration could not be written: {}", e),
+        }
+    }
+
+    /// متن کانفیگ (هر نسخه‌ای) را بدون دست زدن به فایل می‌خواند؛ برای import
+    pub fn parse(txt: &str) -> Result<Self, ConfigError> {
+        let (cfg, _) = migrate(txt)?;
+        cfg.validate()?;
+        Ok(cfg)
+    }
+
+    /// قوانینی که به چیدمان ناشناخته اشاره می‌کنند بی‌صدا کار نمی‌کنند، پس همین‌جا رد می‌شوند
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.settings.poll_ms == 0 {
+            return Err(ConfigError::Invalid("settings.poll_ms must be greater than 0".into()));
+        }
+        if self.settings.learn_confidence == 0 {
+            return Err(ConfigError::Invalid("settings.learn_confidence must be greater than 0".into()));
+        }
+        RuleSet::compile(&self.rules).map_err(ConfigError::Invalid)?;
+        let registry = self.registry();
+        for (i, rule) in self.rules.iter().enumerate() {
+            if registry.get(&rule.lang).is_none() {
+                return Err(ConfigError::Invalid(format!(
+                    "rules[{}] ({}): unknown layout '{}'",
+                    i, rule.process, rule.lang
+                )));
+            }
+        }
+        for (i, l) in self.learned.iter().enumerate() {
+            if registry.get(&l.lang).is_none() {
+                return Err(ConfigError::Invalid(format!(
+                    "learned[{}] ({}): unknown layout '{}'",
+                    i, l.process, l.lang
+                )));
+            }
+        }
+        if registry.get(&self.defaults.lang).is_none() {
+            return Err(ConfigError::Invalid(format!("defaults.lang: unknown layout '{}'", self.defaults.lang)));
+        }
+        Ok(())
+    }
+
+    pub fn save(&mut self) -> Result<(), ConfigError> {
+        self.save_to(&get_config_path())
+    }
+
+    /// ذخیرهٔ کامل؛ اگر از زمان خواندن این نسخه کس دیگری فایل را عوض کرده باشد Conflict برمی‌گردد
+    pub fn save_to(&mut self, path: &Path) -> Result<(), ConfigError> {
+        let _lock = ConfigLock::acquire(path)?;
+        let on_disk = match fs::read_to_string(path) {
+            Ok(txt) => serde_json::from_str::<Value>(&txt)
+                .ok()
+                .and_then(|v| v.get("generation").and_then(Value::as_u64))
+                .unwrap_or(0),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e.into()),
+        };
+        if on_disk != self.generation {
+            return Err(ConfigError::Conflict { ours: self.generation, theirs: on_disk });
+        }
+        self.generation += 1;
+        self.write_atomic(path)?;
+        Ok(())
+    }
+
+    /// read-modify-write زیر قفل: آخرین نسخهٔ روی دیسک خوانده، f روی آن اعمال و ذخیره می‌شود.
+    /// نتیجهٔ نامعتبر نوشته نمی‌شود؛ فایل روی دیسک دست‌نخورده می‌ماند
+    pub fn update<F: FnOnce(&mut Config)>(f: F) -> Result<Config, ConfigError> {
+        Self::update_at(&get_config_path(), f)
+    }
+
+    pub fn update_at<F: FnOnce(&mut Config)>(path: &Path, f: F) -> Result<Config, ConfigError> {
+        let _lock = ConfigLock::acquire(path)?;
+        let mut cfg = match Self::read(path) {
+            Ok((cfg, v0)) => {
+                // بکاپ نسخه 0؛ خود فایل چند خط پایین‌تر نوشته می‌شود
+                if let Some(txt) = v0 {
+                    if let Err(e) = fs::write(backup_path(path), txt) {
+                        eprintln!("Config backup could not be written: {}", e);
+                    }
+                }
+                cfg
+            }
+            Err(ConfigError::Io(e)) if e.kind() == io::ErrorKind::NotFound => Self::default(),
+            // فایلی که کاربر در حال ویرایشش است را رونویسی نکن
+            Err(e) => return Err(e),
+        };
+        f(&mut cfg);
+        cfg.validate()?;
+        cfg.generation += 1;
+        cfg.write_atomic(path)?;
+        Ok(cfg)
+    }
+
+    /// temp + fsync + rename؛ خواننده هیچ‌وقت فایل نیمه‌کاره نمی‌بیند
+    fn write_atomic(&self, path: &Path) -> io::Result<()> {
+        let txt = serde_json::to_string_pretty(self)?;
+        let tmp = sibling(path, ".tmp");
+        {
+            let mut f = fs::File::create(&tmp)?;
+            f.write_all(txt.as_bytes())?;
+            f.sync_all()?;
+        }
+        if let Err(e) = fs::rename(&tmp, path) {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+        #[cfg(unix)]
+        if let Some(dir) = path.parent() {
+            if let Ok(d) = fs::File::open(dir) {
+                let _ = d.sync_all();
+            }
+        }
+        Ok(())
+    }
+
+    pub fn registry(&self) -> LayoutRegistry {
+        LayoutRegistry::new(&self.layouts)
+    }
+
+    /// زبان یک پروسه؛ اول تطابق دقیق، بعد بدون حساسیت به حروف بزرگ و کوچک
+    pub fn lang_for(&self, proc_name: &str) -> Option<&str> {
+        self.rule_index(proc_name).map(|i| self.rules[i].lang.as_str())
+    }
+
+    /// فقط قوانین سادهٔ نام پروسه؛ قوانین مسیر/regex را GUI دست نمی‌زند
+    fn rule_index(&self, proc_name: &str) -> Option<usize> {
+        let plain = |r: &&Rule| r.is_plain();
+        self.rules
+            .iter()
+            .position(|r| plain(&r) && r.process == proc_name)
+            .or_else(|| self.rules.iter().position(|r| plain(&r) && r.process.eq_ignore_ascii_case(proc_name)))
+    }
+
+    /// قانون پروسه را اضافه یا به‌روز می‌کند
+    pub fn set_lang(&mut self, proc_name: &str, lang: &str) {
+        match self.rule_index(proc_name) {
+            Some(i) => self.rules[i].lang = lang.to_string(),
+            None => self.rules.push(Rule::for_process(proc_name, lang)),
+        }
+    }
+
+    /// true اگر قانونی حذف شد
+    pub fn remove_rule(&mut self, proc_name: &str) -> bool {
+        match self.rule_index(proc_name) {
+            Some(i) => {
+                self.rules.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// قوانین یک کانفیگ دیگر را ادغام می‌کند: قانون ساده جای قانون همان پروسه را می‌گیرد،
+    /// قانون شرط‌دار جای قانونی با همان شرط‌ها را، وگرنه به ته لیست اضافه می‌شود
+    pub fn merge_rules(&mut self, rules: &[Rule]) {
+        for rule in rules {
+            if rule.is_plain() {
+                self.set_lang(&rule.process, &rule.lang);
+                continue;
+            }
+            let matchers = |r: &Rule| Rule { lang: String::new(), ..r.clone() };
+            let key = matchers(rule);
+            match self.rules.iter_mut().find(|r| matchers(r) == key) {
+                Some(r) => r.lang = rule.lang.clone(),
+                None => self.rules.push(rule.clone()),
+            }
+        }
+    }
+
+    fn learned_index(&self, proc_name: &str) -> Option<usize> {
+        self.learned.iter().position(|l| l.process.eq_ignore_ascii_case(proc_name))
+    }
+
+    /// زبان یادگرفته‌شده؛ بدون توجه به روشن بودن learning (آن را watcher چک می‌کند)
+    pub fn learned_lang(&self, proc_name: &str) -> Option<&str> {
+        self.learned_index(proc_name).map(|i| self.learned[i].lang.as_str())
+    }
+
+    /// ثبت یا به‌روزرسانی؛ false اگر همین مقدار از قبل بود
+    pub fn learn(&mut self, proc_name: &str, lang: &str) -> bool {
+        match self.learned_index(proc_name) {
+            Some(i) if self.learned[i].lang == lang => false,
+            Some(i) => {
+                self.learned[i].lang = lang.to_string();
+                true
+            }
+            None => {
+                self.learned.push(Learned { process: proc_name.to_string(), lang: lang.to_string() });
+                true
+            }
+        }
+    }
+
+    pub fn forget_learned(&mut self, proc_name: &str) -> bool {
+        match self.learned_index(proc_name) {
+            Some(i) => {
+                self.learned.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// مورد یادگرفته‌شده را به قانون صریح تبدیل می‌کند
+    pub fn promote_learned(&mut self, proc_name: &str) -> bool {
+        let Some(i) = self.learned_index(proc_name) else { return false };
+        let l = self.learned.remove(i);
+        self.set_lang(&l.process, &l.lang);
+        true
+    }
+}
+
+/// `lang_config.json` -> `lang_config.json<suffix>`
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    sibling(path, ".v0.bak")
+}
+
+/// قفل انحصاری روی `lang_config.json.lock`؛ با بسته شدن فایل (یا مردن پروسه) آزاد می‌شود
+struct ConfigLock {
+    _file: fs::File,
+}
+
+impl ConfigLock {
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    fn acquire(path: &Path) -> Result<Self, ConfigError> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(sibling(path, ".lock"))?;
+        let start = Instant::now();
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(Self { _file: file }),
+                Err(TryLockError::WouldBlock) if start.elapsed() < Self::TIMEOUT => {
+                    thread::sleep(Duration::from_millis(20))
+                }
+                Err(TryLockError::WouldBlock) => return Err(ConfigError::Locked),
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+/// متن کانفیگ را به آخرین نسخه می‌رساند؛ مقدار دوم یعنی ارتقا انجام شد
+fn migrate(txt: &str) -> Result<(Config, bool), ConfigError> {
+    let Value::Object(map) = serde_json::from_str::<Value>(txt)? else {
+        return Err(ConfigError::Invalid("top level must be a JSON object".into()));
+    };
+
+    if map.contains_key("version") {
+        // دوباره از متن پارس می‌کنیم تا خطای نوع هم شمارهٔ خط و ستون داشته باشد
+        let cfg: Config = serde_json::from_str(txt)?;
+        if cfg.version > CONFIG_VERSION {
+            eprintln!("Config version {} is newer than supported {}", cfg.version, CONFIG_VERSION);
+        }
+        return Ok((cfg, false));
+    }
+
+    // نسخه 0: {"chrome.exe": "fa", ...}
+    let mut rules: Vec<Rule> = map
+        .into_iter()
+        .filter_map(|(process, lang)| lang.as_str().map(|l| Rule::for_process(&process, l)))
+        .collect();
+    rules.sort_by_key(|r| r.process.to_lowercase());
+    Ok((Config { rules, ..Config::default() }, true))
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    /// JSON خراب یا نوع اشتباه؛ خط و ستون از serde_json
+    Parse { line: usize, column: usize, message: String },
+    Invalid(String),
+    /// فایل بعد از خواندن ما توسط برنامهٔ دیگری ذخیره شده
+    Conflict { ours: u64, theirs: u64 },
+    Locked,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "{}", e),
+            ConfigError::Parse { line, column, message } => {
+                write!(f, "parse error at line {}, column {}: {}", line, column, message)
+            }
+            ConfigError::Invalid(msg) => write!(f, "invalid config: {}", msg),
+            ConfigError::Conflict { ours, theirs } => write!(
+                f,
+                "config was changed by another program (generation {} on disk, ours {}); reload and retry",
+                theirs, ours
+            ),
+            ConfigError::Locked => write!(f, "config file is locked by another program"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ConfigError {
+    fn from(e: serde_json::Error) -> Self {
+        ConfigError::Parse { line: e.line(), column: e.column(), message: e.to_string() }
+    }
+}
+
+/// فایل کانفیگ را فقط وقتی عوض شده دوباره می‌خواند؛ اگر نسخهٔ جدید خراب باشد آخرین نسخهٔ سالم می‌ماند
+pub struct ConfigWatcher {
+    path: PathBuf,
+    stamp: Option<(SystemTime, u64)>,
+    current: Config,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: PathBuf) -> Self {
+        let mut w = Self { path, stamp: None, current: Config::default() };
+        w.poll();
+        w
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.current
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn stamp(&self) -> Option<(SystemTime, u64)> {
+        let meta = fs::metadata(&self.path).ok()?;
+        Some((meta.modified().ok()?, meta.len()))
+    }
+
+    /// بدون نگاه به زمان تغییر فایل دوباره می‌خواند
+    pub fn reload(&mut self) -> bool {
+        self.stamp = None;
+        self.poll()
+    }
+
+    /// true یعنی کانفیگ جدیدی بارگذاری شد
+    pub fn poll(&mut self) -> bool {
+        let stamp = self.stamp();
+        if stamp == self.stamp {
+            return false;
+        }
+        self.stamp = stamp;
+        if stamp.is_none() {
+            return false;
+        }
+
+        match Config::try_load_from(&self.path) {
+            Ok(cfg) => {
+                // ارتقای نسخه خود فایل را عوض می‌کند؛ همان را مبنا بگیر
+                self.stamp = self.stamp();
+                if cfg == self.current {
+                    return false;
+                }
+                println!("Config reloaded ({} rules)", cfg.rules.len());
+                self.current = cfg;
+                true
+            }
+            Err(e) => {
+                eprintln!("Config {} rejected, keeping last good config: {}", self.path.display(), e);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cfg(pairs: &[(&str, &str)]) -> Config {
+        let mut c = Config::default();
+        for (k, v) in pairs {
+            c.set_lang(k, v);
+        }
+        c
+    }
+
+    fn temp_dir(tag: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("langswitcher-{}-{}", tag, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn lang_for_prefers_exact_match() {
+        let rule = Rule::for_process;
+        let c = Config { rules: vec![rule("code.exe", "fa"), rule("Code.exe", "en")], ..Config::default() };
+        assert_eq!(c.lang_for("Code.exe"), Some("en"));
+        assert_eq!(c.lang_for("CODE.EXE"), Some("fa"));
+    }
+
+    #[test]
+    fn lang_for_falls_back_to_case_insensitive() {
+        let c = cfg(&[("chrome.exe", "fa")]);
+        assert_eq!(c.lang_for("CHROME.EXE"), Some("fa"));
+        assert_eq!(c.lang_for("firefox.exe"), None);
+    }
+
+    #[test]
+    fn set_and_remove_rules_ignore_case() {
+        let mut c = cfg(&[("chrome.exe", "fa")]);
+        c.set_lang("Chrome.exe", "en");
+        assert_eq!(c.rules.len(), 1);
+        assert_eq!(c.lang_for("chrome.exe"), Some("en"));
+        assert!(c.remove_rule("CHROME.EXE"));
+        assert!(!c.remove_rule("chrome.exe"));
+        assert!(c.rules.is_empty());
+    }
+
+    #[test]
+    fn load_from_missing_or_broken_file_is_empty() {
+        let dir = temp_dir("cfg");
+        let path = dir.join(CONFIG_FILE);
+        assert_eq!(Config::load_from(&path), Config::default());
+
+        fs::write(&path, "{ not json").unwrap();
+        assert_eq!(Config::load_from(&path), Config::default());
+
+        let mut c = cfg(&[("NSIS.exe", "en")]);
+        c.save_to(&path).unwrap();
+        assert_eq!(Config::load_from(&path), c);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn flat_v0_file_is_migrated_in_place_with_backup() {
+        let dir = temp_dir("migrate");
+        let path = dir.join(CONFIG_FILE);
+        let v0 = r#"{ "chrome.exe": "fa", "Code.exe": "en" }"#;
+        fs::write(&path, v0).unwrap();
+
+        let c = Config::load_from(&path);
+        assert_eq!(c.version, CONFIG_VERSION);
+        assert_eq!(c.lang_for("chrome.exe"), Some("fa"));
+        assert_eq!(c.lang_for("Code.exe"), Some("en"));
+
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), v0);
+        let on_disk: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(on_disk["version"], CONFIG_VERSION);
+        assert_eq!(Config::load_from(&path), c);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn stale_v0_read_does_not_overwrite_a_newer_save() {
+        let dir = temp_dir("migrate_race");
+        let path = dir.join(CONFIG_FILE);
+        fs::write(&path, r#"{ "chrome.exe": "fa" }"#).unwrap();
+        assert!(Config::read(&path).unwrap().1.is_some());
+
+        // برنامهٔ دیگری بین خواندن و ارتقای ما ذخیره کرد
+        Config::update_at(&path, |c| c.set_lang("Code.exe", "en")).unwrap();
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), r#"{ "chrome.exe": "fa" }"#);
+
+        let c = Config::try_load_from(&path).unwrap();
+        assert_eq!(c.generation, 1);
+        assert_eq!(c.lang_for("Code.exe"), Some("en"));
+        assert_eq!(Config::load_from(&path).lang_for("chrome.exe"), Some("fa"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn v1_sections_are_optional() {
+        let (c, migrated) = migrate(r#"{ "version": 1 }"#).unwrap();
+        assert!(!migrated);
+        assert_eq!(c, Config::default());
+    }
+
+    #[test]
+    fn parse_errors_carry_line_and_column() {
+        let txt = "{\n  \"version\": 1,\n  \"rules\": [ { \"process\": 3 } ]\n}";
+        match migrate(txt) {
+            Err(ConfigError::Parse { line, column, .. }) => {
+                assert_eq!(line, 3);
+                assert!(column > 0);
+            }
+            other => panic!("expected parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unknown_layout_fails_validation() {
+        let c = cfg(&[("chrome.exe", "klingon")]);
+        assert!(matches!(c.validate(), Err(ConfigError::Invalid(_))));
+        assert!(cfg(&[("chrome.exe", "fa")]).validate().is_ok());
+    }
+
+    #[test]
+    fn watcher_keeps_last_good_config() {
+        let dir = temp_dir("watch");
+        let path = dir.join(CONFIG_FILE);
+        cfg(&[("chrome.exe", "fa")]).save_to(&path).unwrap();
+
+        let mut w = ConfigWatcher::new(path.clone());
+        assert_eq!(w.config().lang_for("chrome.exe"), Some("fa"));
+        assert!(!w.poll(), "unchanged file must not reload");
+
+        fs::write(&path, r#"{ "version": 1, "rules": [ { "process": "chrome.exe", "#).unwrap();
+        assert!(!w.poll());
+        assert_eq!(w.config().lang_for("chrome.exe"), Some("fa"));
+
+        // ویرایشگر کاربر بقیهٔ فایل را می‌نویسد
+        fs::write(&path, r#"{ "version": 1, "rules": [ { "process": "chrome.exe", "lang": "en" } ] }"#).unwrap();
+        assert!(w.poll());
+        assert_eq!(w.config().lang_for("chrome.exe"), Some("en"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn save_is_atomic_and_bumps_generation() {
+        let dir = temp_dir("atomic");
+        let path = dir.join(CONFIG_FILE);
+        let mut c = cfg(&[("chrome.exe", "fa")]);
+        c.save_to(&path).unwrap();
+        assert_eq!(c.generation, 1);
+        assert!(!sibling(&path, ".tmp").exists());
+        assert_eq!(Config::load_from(&path).generation, 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn stale_save_is_rejected_but_update_merges() {
+        let dir = temp_dir("conflict");
+        let path = dir.join(CONFIG_FILE);
+        cfg(&[("chrome.exe", "fa")]).save_to(&path).unwrap();
+
+        // GUI و watcher هر دو نسخهٔ 1 را خوانده‌اند
+        let mut gui = Config::load_from(&path);
+        Config::update_at(&path, |c| c.set_lang("Code.exe", "en")).unwrap();
+
+        gui.set_lang("firefox.exe", "en");
+        assert!(matches!(gui.save_to(&path), Err(ConfigError::Conflict { ours: 1, theirs: 2 })));
+
+        Config::update_at(&path, |c| c.set_lang("firefox.exe", "en")).unwrap();
+        let on_disk = Config::load_from(&path);
+        assert_eq!(on_disk.lang_for("Code.exe"), Some("en"));
+        assert_eq!(on_disk.lang_for("firefox.exe"), Some("en"));
+        assert_eq!(on_disk.lang_for("chrome.exe"), Some("fa"));
+        assert_eq!(on_disk.generation, 3);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn invalid_update_is_not_written() {
+        let dir = temp_dir("invalid_update");
+        let path = dir.join(CONFIG_FILE);
+        cfg(&[("chrome.exe", "fa")]).save_to(&path).unwrap();
+
+        let res = Config::update_at(&path, |c| c.set_lang("Code.exe", "xx"));
+        assert!(matches!(res, Err(ConfigError::Invalid(_))));
+        let on_disk = Config::load_from(&path);
+        assert_eq!(on_disk.lang_for("Code.exe"), None);
+        assert_eq!(on_disk.generation, 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn learned_entries_stay_apart_until_promoted() {
+        let mut c = cfg(&[("chrome.exe", "fa")]);
+        assert!(c.learn("Telegram.exe", "fa"));
+        assert!(!c.learn("telegram.exe", "fa"));
+        assert_eq!(c.lang_for("Telegram.exe"), None);
+        assert_eq!(c.learned_lang("TELEGRAM.EXE"), Some("fa"));
+
+        let txt = serde_json::to_string(&c).unwrap();
+        assert!(txt.contains("\"learned\""));
+        assert_eq!(serde_json::from_str::<Config>(&txt).unwrap(), c);
+
+        assert!(c.promote_learned("telegram.exe"));
+        assert!(c.learned.is_empty());
+        assert_eq!(c.lang_for("Telegram.exe"), Some("fa"));
+        assert!(!c.forget_learned("Telegram.exe"));
+    }
+
+    #[test]
+    fn unmapped_policy_is_lowercase_and_optional() {
+        let c: Config = serde_json::from_str(r#"{"version":1,"defaults":{"lang":"fa","unmapped":"restore"}}"#).unwrap();
+        assert_eq!(c.defaults.unmapped, UnmappedPolicy::Restore);
+        let c: Config = serde_json::from_str(r#"{"version":1,"defaults":{"lang":"fa"}}"#).unwrap();
+        assert_eq!(c.defaults.unmapped, UnmappedPolicy::Leave);
+        assert!(serde_json::from_str::<Config>(r#"{"version":1,"defaults":{"unmapped":"nope"}}"#).is_err());
+    }
+
+    #[test]
+    fn merge_replaces_matching_rules_and_appends_new_ones() {
+        let mut c = cfg(&[("chrome.exe", "fa")]);
+        let glob = Rule { path_glob: Some("/opt/**/python".into()), lang: "en".into(), ..Rule::default() };
+        c.rules.push(glob.clone());
+
+        let incoming = vec![
+            Rule::for_process("CHROME.EXE", "en"),
+            Rule { lang: "fa".into(), ..glob.clone() },
+            Rule { title: Some("Jira".into()), process: "chrome.exe".into(), lang: "en".into(), ..Rule::default() },
+        ];
+        c.merge_rules(&incoming);
+        assert_eq!(c.rules.len(), 3);
+        assert_eq!(c.lang_for("chrome.exe"), Some("en"));
+        assert_eq!(c.rules[1].lang, "fa");
+        assert_eq!(c.rules[2].title.as_deref(), Some("Jira"));
+    }
+
+    #[test]
+    fn parse_accepts_v0_without_touching_disk() {
+        let c = Config::parse(r#"{ "chrome.exe": "fa" }"#).unwrap();
+        assert_eq!(c.lang_for("chrome.exe"), Some("fa"));
+        assert!(matches!(Config::parse(r#"{ "version": 1, "rules": [{ "process": "a", "lang": "zz" }] }"#), Err(ConfigError::Invalid(_))));
+    }
+}
@@ -1,44 +1,89 @@
 // src/core.rs
-// src/lib.rs یا src/core.rs
-use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
-use std::fs;
 use std::path::{Path, PathBuf};
-use sysinfo::{ProcessExt, System, SystemExt, PidExt};
+use sysinfo::{ProcessExt, ProcessRefreshKind, System, SystemExt, PidExt};
 use std::env;
 
-pub fn get_config_path() -> PathBuf {
-    let mut dir = dirs::config_dir().unwrap_or_else(|| env::temp_dir());
-    dir.push("LangSwitcher");
-    fs::create_dir_all(&dir).ok();
-    let cfg_path = dir.join("lang_config.json");
-
-    // نسخه پیش‌فرض را از assets کپی کن
-    if !cfg_path.exists() {
-        if let Ok(exe) = env::current_exe() {
-            if let Some(parent) = exe.parent() {
-                let default = parent.join("assets").join("lang_config.json");
-                if default.exists() {
-                    let _ = fs::copy(default, &cfg_path);
-                }
-            }
-        }
-    }
-    cfg_path
-}
+use crate::config::{Config, ConfigError, Learned, UnmappedPolicy};
+use crate::layouts::LayoutRegistry;
 
-#[derive(Serialize, Deserialize, Debug, Default)]
-pub struct Config(pub HashMap<String, String>);
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Program {
     pub name: String,
-    pub lang: String,
+    /// None = قانونی ندارد (با «قانون en» فرق دارد)
+    pub lang: Option<String>,
+    pub exe_path: Option<PathBuf>,
+    /// false = فقط در کانفیگ قانون دارد و الان اجرا نمی‌شود
+    pub running: bool,
+}
+
+/// یک ردیف جدول پروسه‌ها؛ از sysinfo، یا در تست و بنچمارک ساختگی
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcInfo {
+    pub pid: u32,
+    pub name: String,
     pub exe_path: Option<PathBuf>,
 }
 
+/// تفاوت لیست برنامه‌ها با دفعهٔ قبل، تا GUI فقط همان‌ها را به‌روز کند
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgramEvent {
+    Added(Program),
+    Removed(Program),
+    /// همان برنامه با زبان یا وضعیت اجرای دیگر
+    Changed(Program),
+}
+
+/// `System` را نگه می‌دارد تا هر refresh فقط جدول پروسه‌ها تازه شود، نه کل سیستم
+pub struct ProcessScanner {
+    sys: System,
+}
+
+impl Default for ProcessScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProcessScanner {
+    pub fn new() -> Self {
+        Self { sys: System::new() }
+    }
+
+    /// مرتب بر اساس pid تا ترتیب نتیجه ثابت باشد
+    pub fn scan(&mut self) -> Vec<ProcInfo> {
+        self.sys.refresh_processes_specifics(ProcessRefreshKind::new());
+        let mut procs: Vec<ProcInfo> = self.sys.processes().values().map(|p| {
+            let exe = p.exe();
+            ProcInfo {
+                pid: p.pid().as_u32(),
+                name: p.name().to_string(),
+                exe_path: if exe.as_os_str().is_empty() { None } else { Some(exe.to_path_buf()) },
+            }
+        }).collect();
+        procs.sort_by_key(|p| p.pid);
+        procs
+    }
+}
+
 pub struct LangState {
     pub programs: Vec<Program>,
+    pub registry: LayoutRegistry,
+    /// `settings.learning` و مواردی که watcher یاد گرفته، برای مرور در GUI
+    pub learning: bool,
+    pub learned: Vec<Learned>,
+    /// `settings.per_window`
+    pub per_window: bool,
+    /// `defaults.unmapped` و `defaults.lang`
+    pub unmapped: UnmappedPolicy,
+    pub default_lang: String,
+    scanner: ProcessScanner,
+}
+
+impl Default for LangState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl LangState {
@@ -56,8 +101,6 @@ impl LangState {
 
     #[cfg(target_os = "windows")]
     fn visible_window_pids() -> HashSet<u32> {
-        use std::collections::HashSet;
-        use std::ptr::null_mut;
         use winapi::shared::minwindef::{BOOL, DWORD, LPARAM, TRUE};
         use winapi::shared::windef::HWND;
         use winapi::um::winuser::{EnumWindows, GetWindowTextLengthW, IsWindowVisible, GetWindowThreadProcessId};
@@ -87,7 +130,8 @@ impl LangState {
     #[cfg(not(target_os = "windows"))]
     fn visible_window_pids() -> HashSet<u32> { HashSet::new() }
 
-    fn should_skip_process(proc_name: &str, exe_path_opt: Option<&PathBuf>) -> bool {
+    /// `self_name` نام exe خود برنامه است؛ یک بار حساب می‌شود نه برای هر پروسه
+    fn should_skip_process(proc_name: &str, exe_path_opt: Option<&Path>, self_name: Option<&str>) -> bool {
         let blacklist_names = [
             "explorer.exe", "shellexperiencehost.exe", "systemsettings.exe",
             "applicationframehost.exe", "searchui.exe", "startmenuexperiencehost.exe",
@@ -110,138 +154,249 @@ impl LangState {
             }
         }
 
-        if let Ok(current) = env::current_exe() {
-            if let Some(cur_name) = current.file_name().and_then(|n| n.to_str()) {
-                if cur_name.eq_ignore_ascii_case(proc_name) { return true; }
-            }
-        }
-
-        false
+        self_name.is_some_and(|me| me.eq_ignore_ascii_case(proc_name))
     }
 
     pub fn new() -> Self {
-        let mut sys = System::new_all();
-        sys.refresh_processes();
-
-        let cfg: Config = fs::read_to_string(get_config_path())
-            .ok()
-            .and_then(|s| serde_json::from_str(&s).ok())
-            .unwrap_or_default();
-
-        let visible_pids = Self::visible_window_pids();
-        let filter_by_windows = !visible_pids.is_empty();
+        Self::from_config(Config::load())
+    }
 
-        let mut seen: HashSet<String> = HashSet::new();
-        let mut progs: Vec<Program> = Vec::new();
+    /// لیست برنامه‌های در حال اجرا با قوانین همین کانفیگ (CLI با `--config` از این استفاده می‌کند)
+    pub fn from_config(cfg: Config) -> Self {
+        let mut state = Self {
+            programs: Vec::new(),
+            registry: cfg.registry(),
+            learning: false,
+            learned: Vec::new(),
+            per_window: false,
+            unmapped: UnmappedPolicy::default(),
+            default_lang: String::new(),
+            scanner: ProcessScanner::new(),
+        };
+        state.rescan(&cfg);
+        state.apply_config(cfg);
+        state
+    }
 
-        for &pname in Self::predefined_list().iter() {
-            for (_pid, proc_) in sys.processes() {
-                if proc_.name().eq_ignore_ascii_case(pname) {
-                    let pid_u = proc_.pid().as_u32();
-                    if filter_by_windows && !visible_pids.contains(&pid_u) { continue; }
+    /// پروسه‌ها و کانفیگ را دوباره می‌خواند؛ فقط تغییرات نسبت به لیست قبلی برگردانده می‌شود
+    pub fn refresh(&mut self) -> Vec<ProgramEvent> {
+        let cfg = Config::load();
+        let events = self.rescan(&cfg);
+        self.apply_config(cfg);
+        events
+    }
 
-                    let proc_name = proc_.name().to_string();
-                    let exe = proc_.exe();
-                    let exe_opt = if exe.as_os_str().is_empty() { None } else { Some(exe.to_path_buf()) };
+    fn rescan(&mut self, cfg: &Config) -> Vec<ProgramEvent> {
+        let procs = self.scanner.scan();
+        let programs = collect_programs(&procs, &Self::visible_window_pids(), cfg);
+        let events = diff_programs(&self.programs, &programs);
+        self.programs = programs;
+        events
+    }
 
-                    if Self::should_skip_process(&proc_name, exe_opt.as_ref()) { break; }
+    fn apply_config(&mut self, cfg: Config) {
+        self.registry = cfg.registry();
+        self.learning = cfg.settings.learning;
+        self.learned = cfg.learned;
+        self.per_window = cfg.settings.per_window;
+        self.unmapped = cfg.defaults.unmapped;
+        self.default_lang = cfg.defaults.lang;
+    }
 
-                    let key = exe_opt.as_ref()
-                        .map(|p| p.to_string_lossy().to_string().to_lowercase())
-                        .unwrap_or_else(|| proc_name.to_lowercase());
-                    if seen.contains(&key) { break; }
+    /// فقط قانون همین برنامه روی آخرین نسخهٔ دیسک نوشته می‌شود؛ ویرایش‌های CLI و watcher سر جایشان می‌مانند
+    pub fn set_rule(&mut self, name: &str, lang: &str) -> Result<(), ConfigError> {
+        Config::update(|cfg| cfg.set_lang(name, lang))?;
+        for p in self.programs.iter_mut().filter(|p| p.name.eq_ignore_ascii_case(name)) {
+            p.lang = Some(lang.to_string());
+        }
+        Ok(())
+    }
 
-                    let lang = cfg.0.get(&proc_name).cloned().unwrap_or_else(|| "en".to_string());
-                    progs.push(Program { name: proc_name.clone(), lang, exe_path: exe_opt });
-                    seen.insert(key);
-                    break;
-                }
-            }
+    /// قانون برنامه را از کانفیگ حذف می‌کند؛ ردیف‌های «not running» هم از لیست می‌روند
+    pub fn remove_rule(&mut self, name: &str) -> Result<(), ConfigError> {
+        Config::update(|cfg| { cfg.remove_rule(name); })?;
+        self.programs.retain(|p| p.running || !p.name.eq_ignore_ascii_case(name));
+        for p in self.programs.iter_mut().filter(|p| p.name.eq_ignore_ascii_case(name)) {
+            p.lang = None;
         }
+        Ok(())
+    }
 
-        self_fill_other_processes(&mut progs, &mut seen, filter_by_windows, &cfg, &mut sys);
+    pub fn set_unmapped(&mut self, policy: UnmappedPolicy, default_lang: &str) -> Result<(), ConfigError> {
+        let cfg = Config::update(|cfg| {
+            cfg.defaults.unmapped = policy;
+            cfg.defaults.lang = default_lang.to_string();
+        })?;
+        self.unmapped = cfg.defaults.unmapped;
+        self.default_lang = cfg.defaults.lang;
+        Ok(())
+    }
 
-        Self { programs: progs }
+    pub fn set_learning(&mut self, on: bool) -> Result<(), ConfigError> {
+        let cfg = Config::update(|cfg| cfg.settings.learning = on)?;
+        self.learning = cfg.settings.learning;
+        self.learned = cfg.learned;
+        Ok(())
     }
 
-    pub fn save_config(&self) -> std::io::Result<()> {
-        let mut cfg = Config::default();
-        for p in &self.programs { cfg.0.insert(p.name.clone(), p.lang.clone()); }
-        let txt = serde_json::to_string_pretty(&cfg)?;
-        fs::write(get_config_path(), txt)?;
+    pub fn set_per_window(&mut self, on: bool) -> Result<(), ConfigError> {
+        let cfg = Config::update(|cfg| cfg.settings.per_window = on)?;
+        self.per_window = cfg.settings.per_window;
         Ok(())
     }
 
-    pub fn refresh(&mut self) {
-        let cfg: Config = fs::read_to_string(get_config_path())
-            .ok()
-            .and_then(|s| serde_json::from_str(&s).ok())
-            .unwrap_or_default();
-
-        // همان منطق ساخت پروسه‌ها
-        let mut sys = System::new_all();
-        sys.refresh_processes();
-
-        let visible_pids = Self::visible_window_pids();
-        let filter_by_windows = !visible_pids.is_empty();
-
-        let mut seen: HashSet<String> = HashSet::new();
-        let mut progs: Vec<Program> = Vec::new();
-
-        for &pname in Self::predefined_list().iter() {
-            for (_pid, proc_) in sys.processes() {
-                if proc_.name().eq_ignore_ascii_case(pname) {
-                    let pid_u = proc_.pid().as_u32();
-                    if filter_by_windows && !visible_pids.contains(&pid_u) { continue; }
-                    let proc_name = proc_.name().to_string();
-                    let exe = proc_.exe();
-                    let exe_opt = if exe.as_os_str().is_empty() { None } else { Some(exe.to_path_buf()) };
-                    if Self::should_skip_process(&proc_name, exe_opt.as_ref()) { break; }
-
-                    let key = exe_opt.as_ref()
-                        .map(|p| p.to_string_lossy().to_string().to_lowercase())
-                        .unwrap_or_else(|| proc_name.to_lowercase());
-                    if seen.contains(&key) { break; }
-
-                    let lang = self.programs.iter().find(|x| x.name.eq_ignore_ascii_case(&proc_name))
-                        .map(|x| x.lang.clone())
-                        .or_else(|| cfg.0.get(&proc_name).cloned())
-                        .unwrap_or_else(|| "en".to_string());
-
-                    progs.push(Program { name: proc_name.clone(), lang, exe_path: exe_opt });
-                    seen.insert(key);
-                    break;
-                }
+    /// مورد یادگرفته را قانون صریح می‌کند؛ ردیف برنامه هم همان زبان را نشان می‌دهد
+    pub fn promote_learned(&mut self, name: &str) -> Result<(), ConfigError> {
+        let cfg = Config::update(|cfg| {
+            cfg.promote_learned(name);
+        })?;
+        if let Some(lang) = cfg.lang_for(name) {
+            for p in self.programs.iter_mut().filter(|p| p.name.eq_ignore_ascii_case(name)) {
+                p.lang = Some(lang.to_string());
             }
         }
+        self.learned = cfg.learned;
+        Ok(())
+    }
+
+    pub fn forget_learned(&mut self, name: &str) -> Result<(), ConfigError> {
+        let cfg = Config::update(|cfg| {
+            cfg.forget_learned(name);
+        })?;
+        self.learned = cfg.learned;
+        Ok(())
+    }
+}
+
+/// کلید یکتایی برنامه: مسیر exe، یا نام وقتی مسیر معلوم نیست
+fn program_key(name: &str, exe_path: Option<&Path>) -> String {
+    exe_path.map(|p| p.to_string_lossy().to_lowercase()).unwrap_or_else(|| name.to_lowercase())
+}
+
+/// یک گذر روی جدول پروسه‌ها: برنامه‌های پیش‌فرض اول (به ترتیب `predefined_list`)، بقیه بر اساس نام،
+/// و در آخر قوانینی که برنامه‌شان اجرا نمی‌شود. `visible` خالی یعنی فیلتر پنجره نداریم (غیر ویندوز).
+pub fn collect_programs(procs: &[ProcInfo], visible: &HashSet<u32>, cfg: &Config) -> Vec<Program> {
+    let predefined = LangState::predefined_list();
+    let current = env::current_exe().ok();
+    let self_name = current.as_deref().and_then(Path::file_name).and_then(|n| n.to_str());
+
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut first: Vec<Option<Program>> = vec![None; predefined.len()];
+    let mut other: Vec<Program> = Vec::new();
+    for p in procs {
+        if !visible.is_empty() && !visible.contains(&p.pid) { continue; }
+        if p.name.trim().is_empty() { continue; }
+        let exe = p.exe_path.as_deref();
+        if LangState::should_skip_process(&p.name, exe, self_name) { continue; }
+        if !seen.insert(program_key(&p.name, exe)) { continue; }
+
+        let prog = Program {
+            name: p.name.clone(),
+            lang: cfg.lang_for(&p.name).map(str::to_string),
+            exe_path: p.exe_path.clone(),
+            running: true,
+        };
+        match predefined.iter().position(|n| n.eq_ignore_ascii_case(&p.name)) {
+            Some(i) if first[i].is_none() => first[i] = Some(prog),
+            _ => other.push(prog),
+        }
+    }
+    other.sort_by_key(|p| p.name.to_lowercase());
 
-        self_fill_other_processes(&mut progs, &mut seen, filter_by_windows, &cfg, &mut sys);
+    let mut progs: Vec<Program> = first.into_iter().flatten().collect();
+    progs.extend(other);
+    fill_not_running(&mut progs, cfg);
+    progs
+}
 
-        self.programs = progs;
+/// برنامه‌ای که از «اجرا» به «not running» می‌رود کلیدش عوض می‌شود و Removed + Added حساب می‌شود
+pub fn diff_programs(old: &[Program], new: &[Program]) -> Vec<ProgramEvent> {
+    let key = |p: &Program| program_key(&p.name, p.exe_path.as_deref());
+    let before: HashMap<String, &Program> = old.iter().map(|p| (key(p), p)).collect();
+    let after: HashSet<String> = new.iter().map(key).collect();
+
+    let mut events = Vec::new();
+    for p in new {
+        match before.get(&key(p)) {
+            None => events.push(ProgramEvent::Added(p.clone())),
+            Some(o) if *o != p => events.push(ProgramEvent::Changed(p.clone())),
+            Some(_) => {}
+        }
     }
+    events.extend(old.iter().filter(|p| !after.contains(&key(p))).cloned().map(ProgramEvent::Removed));
+    events
 }
 
-// helper function برای سایر پروسه‌ها
-fn self_fill_other_processes(progs: &mut Vec<Program>, seen: &mut HashSet<String>, filter_by_windows: bool, cfg: &Config, sys: &mut System) {
-    let mut other: Vec<(String, Option<PathBuf>, u32)> = Vec::new();
-    for (_pid, proc_) in sys.processes() {
-        let pid_u = proc_.pid().as_u32();
-        if filter_by_windows && !LangState::visible_window_pids().contains(&pid_u) { continue; }
-        let name = proc_.name().to_string();
-        if name.trim().is_empty() { continue; }
-        let exe = proc_.exe();
-        let exe_opt = if exe.as_os_str().is_empty() { None } else { Some(exe.to_path_buf()) };
-        if LangState::should_skip_process(&name, exe_opt.as_ref()) { continue; }
-        let key = exe_opt.as_ref()
-            .map(|p| p.to_string_lossy().to_string().to_lowercase())
-            .unwrap_or_else(|| name.to_lowercase());
-        if seen.contains(&key) { continue; }
-        seen.insert(key.clone());
-        other.push((name, exe_opt, pid_u));
-    }
-    other.sort_by(|a, b| a.0.to_lowercase().cmp(&b.0.to_lowercase()));
-    for (name, exe_opt, _) in other {
-        let lang = cfg.0.get(&name).cloned().unwrap_or_else(|| "en".to_string());
-        progs.push(Program { name, lang, exe_path: exe_opt });
+// قوانینی که برنامه‌شان الان اجرا نمی‌شود، به صورت ردیف «not running»
+// (فقط قوانین سادهٔ نام پروسه؛ قوانین مسیر/regex فقط در فایل کانفیگ ویرایش می‌شوند)
+fn fill_not_running(progs: &mut Vec<Program>, cfg: &Config) {
+    let mut missing: Vec<Program> = cfg.rules.iter()
+        .filter(|r| r.is_plain())
+        .filter(|r| !progs.iter().any(|p| p.name.eq_ignore_ascii_case(&r.process)))
+        .map(|r| Program { name: r.process.clone(), lang: Some(r.lang.clone()), exe_path: None, running: false })
+        .collect();
+    missing.sort_by_key(|p| p.name.to_lowercase());
+    progs.extend(missing);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proc_(pid: u32, name: &str, exe: Option<&str>) -> ProcInfo {
+        ProcInfo { pid, name: name.to_string(), exe_path: exe.map(PathBuf::from) }
+    }
+
+    fn names(progs: &[Program]) -> Vec<(&str, bool)> {
+        progs.iter().map(|p| (p.name.as_str(), p.running)).collect()
+    }
+
+    #[test]
+    fn single_pass_keeps_the_old_ordering() {
+        let mut cfg = Config::default();
+        cfg.set_lang("Telegram.exe", "fa");
+        cfg.set_lang("Slack.exe", "en");
+        let procs = vec![
+            proc_(1, "zed.exe", Some("C:/Apps/zed.exe")),
+            proc_(2, "chrome.exe", Some("C:/Chrome/chrome.exe")),
+            proc_(3, "chrome.exe", Some("C:/Chrome/chrome.exe")),
+            proc_(4, "Code.exe", Some("C:/Code/Code.exe")),
+            proc_(5, "Telegram.exe", None),
+            proc_(6, "explorer.exe", Some("C:/explorer.exe")),
+            proc_(7, "svchost.exe", Some("C:/Windows/System32/svchost.exe")),
+            proc_(8, "   ", None),
+        ];
+        let progs = collect_programs(&procs, &HashSet::new(), &cfg);
+        assert_eq!(names(&progs), vec![
+            ("Code.exe", true),
+            ("chrome.exe", true),
+            ("Telegram.exe", true),
+            ("zed.exe", true),
+            ("Slack.exe", false),
+        ]);
+        assert_eq!(progs[2].lang.as_deref(), Some("fa"));
+    }
+
+    #[test]
+    fn visible_windows_filter_processes() {
+        let procs = vec![proc_(1, "a.exe", None), proc_(2, "b.exe", None)];
+        let visible: HashSet<u32> = [2].into_iter().collect();
+        let progs = collect_programs(&procs, &visible, &Config::default());
+        assert_eq!(names(&progs), vec![("b.exe", true)]);
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_changed() {
+        let mut cfg = Config::default();
+        let before = collect_programs(&[proc_(1, "a.exe", None), proc_(2, "b.exe", None)], &HashSet::new(), &cfg);
+        assert!(diff_programs(&before, &before).is_empty());
+
+        cfg.set_lang("b.exe", "fa");
+        let after = collect_programs(&[proc_(2, "b.exe", None), proc_(3, "c.exe", None)], &HashSet::new(), &cfg);
+        let events = diff_programs(&before, &after);
+        assert_eq!(events.len(), 3);
+        assert!(matches!(&events[0], ProgramEvent::Changed(p) if p.name == "b.exe" && p.lang.as_deref() == Some("fa")));
+        assert!(matches!(&events[1], ProgramEvent::Added(p) if p.name == "c.exe"));
+        assert!(matches!(&events[2], ProgramEvent::Removed(p) if p.name == "a.exe"));
     }
 }
@@ -0,0 +1,62 @@
+// src/instance.rs
+// هر باینری (watcher و GUI) برای هر کاربر فقط یک نسخه؛ با قفل فایل که با بسته شدن پروسه (حتی کرش) آزاد می‌شود.
+// نسخهٔ دوم آرگومان‌هایش را از طریق IPC به نسخهٔ اجرا شده می‌دهد و خارج می‌شود.
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::PathBuf;
+
+/// تا زمانی که زنده است قفل نگه داشته می‌شود
+#[derive(Debug)]
+pub struct SingleInstance {
+    _file: File,
+}
+
+/// مسیری مخصوص کاربر فعلی برای فایل‌های زمان اجرا (سوکت، قفل)
+pub fn runtime_file(name: &str) -> PathBuf {
+    match dirs::runtime_dir() {
+        Some(dir) => dir.join(name),
+        None => {
+            // روی ویندوز TEMP از قبل مخصوص کاربر است؛ روی یونیکس بدون XDG_RUNTIME_DIR نام کاربر را اضافه کن
+            let user = std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_default();
+            let (stem, ext) = name.rsplit_once('.').unwrap_or((name, ""));
+            std::env::temp_dir().join(format!("{}-{}.{}", stem, user, ext))
+        }
+    }
+}
+
+/// پوشهٔ runtime بین همهٔ برنامه‌های کاربر مشترک است؛ مثل endpointهای IPC پیشوند می‌گیرد
+fn lock_path(name: &str) -> PathBuf {
+    runtime_file(&format!("langswitcher-{}.lock", name))
+}
+
+/// None یعنی نسخهٔ دیگری از `name` برای همین کاربر در حال اجراست
+pub fn acquire(name: &str) -> io::Result<Option<SingleInstance>> {
+    let path = lock_path(name);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new().create(true).truncate(false).write(true).open(&path)?;
+    match file.try_lock() {
+        Ok(()) => Ok(Some(SingleInstance { _file: file })),
+        Err(fs::TryLockError::WouldBlock) => Ok(None),
+        Err(fs::TryLockError::Error(e)) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_acquire_fails_until_first_is_dropped() {
+        let name = format!("test-{}", std::process::id());
+        let first = acquire(&name).unwrap();
+        assert!(first.is_some());
+        assert!(acquire(&name).unwrap().is_none());
+        drop(first);
+        assert!(acquire(&name).unwrap().is_some());
+        let file = lock_path(&name).file_name().unwrap().to_string_lossy().into_owned();
+        assert!(file.starts_with("langswitcher-test-"), "{}", file);
+        let _ = fs::remove_file(lock_path(&name));
+    }
+}
@@ -0,0 +1,382 @@
+// src/ipc.rs
+// کانال محلی بین watcher و GUI/CLI؛ به جای پیدا کردن watcher با اسم پروسه.
+//
+// پروتکل: هر خط یک JSON. درخواست `{"v":1,"cmd":"status"}` و پاسخ `{"v":1,"ok":true,...}`.
+// روی یونیکس سوکت دامنه در XDG_RUNTIME_DIR و روی ویندوز named pipe مخصوص همان کاربر.
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::watch::LayoutEvent;
+
+pub const VERSION: u32 = 1;
+
+/// نام endpoint هر باینری
+pub const WATCHER: &str = "langswitcher";
+pub const GUI: &str = "langswitcher-gui";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Command {
+    Ping,
+    Status,
+    Pause,
+    Resume,
+    /// کانفیگ را همین حالا دوباره بخوان
+    Reload,
+    /// پنجرهٔ فعال را دوباره بررسی کن، انگار تازه فوکوس گرفته
+    ApplyNow,
+    Shutdown,
+    /// نسخهٔ دوم برنامه اجرا شد؛ آرگومان‌هایش (مثل `--settings`) به نسخهٔ اول می‌رسد
+    Activate {
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Request {
+    pub v: u32,
+    #[serde(flatten)]
+    pub command: Command,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Status {
+    pub pid: u32,
+    pub paused: bool,
+    /// برنامهٔ فعال در آخرین بررسی
+    #[serde(default)]
+    pub app: Option<String>,
+    /// چیدمانی که watcher فکر می‌کند فعال است
+    #[serde(default)]
+    pub layout: Option<String>,
+    /// چیدمانی که قوانین برای برنامهٔ فعال می‌خواهند
+    #[serde(default)]
+    pub target: Option<String>,
+    pub config_path: PathBuf,
+    pub generation: u64,
+    pub rules: usize,
+    /// آخرین تعویض‌های دستی و تعویض‌های تأییدنشده، قدیمی‌ترین اول
+    #[serde(default)]
+    pub events: Vec<LayoutEvent>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Response {
+    pub v: u32,
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+}
+
+impl Response {
+    pub fn ok() -> Self {
+        Self { v: VERSION, ok: true, error: None, status: None }
+    }
+
+    pub fn with_status(status: Status) -> Self {
+        Self { status: Some(status), ..Self::ok() }
+    }
+
+    pub fn error(msg: impl Into<String>) -> Self {
+        Self { v: VERSION, ok: false, error: Some(msg.into()), status: None }
+    }
+}
+
+pub type Handler = Arc<dyn Fn(Command) -> Response + Send + Sync>;
+
+/// یک خط درخواست را پاسخ می‌دهد؛ نسخهٔ ناشناخته یا فرمان ناشناخته پاسخ خطا می‌گیرد نه قطع اتصال
+pub fn dispatch(line: &str, handler: &dyn Fn(Command) -> Response) -> Response {
+    let value: Value = match serde_json::from_str(line) {
+        Ok(v) => v,
+        Err(e) => return Response::error(format!("bad request: {}", e)),
+    };
+    match value.get("v").and_then(Value::as_u64) {
+        Some(v) if v == VERSION as u64 => {}
+        Some(v) => return Response::error(format!("unsupported protocol version {} (watcher speaks {})", v, VERSION)),
+        None => return Response::error("bad request: missing protocol version"),
+    }
+    match serde_json::from_value::<Request>(value) {
+        Ok(req) => handler(req.command),
+        Err(e) => Response::error(format!("bad request: {}", e)),
+    }
+}
+
+fn serve_connection(reader: impl io::Read, mut writer: impl Write, handler: &Handler) {
+    for line in BufReader::new(reader).lines() {
+        let Ok(line) = line else { return };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let resp = dispatch(&line, handler.as_ref());
+        let Ok(txt) = serde_json::to_string(&resp) else { return };
+        if writeln!(writer, "{}", txt).and_then(|_| writer.flush()).is_err() {
+            return;
+        }
+    }
+}
+
+/// endpoint watcher برای کاربر فعلی
+pub fn endpoint() -> PathBuf {
+    endpoint_for(WATCHER)
+}
+
+pub fn endpoint_for(name: &str) -> PathBuf {
+    #[cfg(windows)]
+    {
+        let user = std::env::var("USERNAME").unwrap_or_default();
+        PathBuf::from(format!(r"\\.\pipe\{}-{}", name, user))
+    }
+    #[cfg(not(windows))]
+    {
+        crate::instance::runtime_file(&format!("{}.sock", name))
+    }
+}
+
+/// سرور IPC (watcher یا GUI)؛ تا وقتی زنده است به درخواست‌ها جواب می‌دهد
+pub struct Server {
+    #[cfg_attr(windows, allow(dead_code))]
+    path: PathBuf,
+}
+
+impl Server {
+    pub fn bind(handler: Handler) -> io::Result<Self> {
+        Self::bind_at(&endpoint(), handler)
+    }
+
+    #[cfg(unix)]
+    pub fn bind_at(path: &Path, handler: Handler) -> io::Result<Self> {
+        use std::os::unix::fs::PermissionsExt;
+        use std::os::unix::net::{UnixListener, UnixStream};
+        use std::time::Duration;
+
+        if path.exists() {
+            // سوکت به‌جا‌مانده از پروسه‌ای که کرش کرده را پاک کن، ولی نسخهٔ زنده را نه
+            if UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(io::ErrorKind::AddrInUse, "another instance is listening"));
+            }
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                let handler = Arc::clone(&handler);
+                thread::spawn(move || {
+                    // کلاینتی که چیزی نمی‌فرستد thread را برای همیشه نگه ندارد
+                    let _ = stream.set_read_timeout(Some(Duration::from_secs(30)));
+                    if let Ok(reader) = stream.try_clone() {
+                        serve_connection(reader, stream, &handler);
+                    }
+                });
+            }
+        });
+        Ok(Self { path: path.to_path_buf() })
+    }
+
+    #[cfg(windows)]
+    pub fn bind_at(path: &Path, handler: Handler) -> io::Result<Self> {
+        let first = pipe::create(path, true)?;
+        let name = path.to_path_buf();
+        thread::spawn(move || {
+            let mut next = Some(first);
+            loop {
+                let pipe = match next.take().map_or_else(|| pipe::create(&name, false), Ok) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        eprintln!("IPC pipe could not be created: {}", e);
+                        return;
+                    }
+                };
+                let Ok(file) = pipe::accept(pipe) else { continue };
+                let handler = Arc::clone(&handler);
+                thread::spawn(move || {
+                    if let Ok(reader) = file.try_clone() {
+                        serve_connection(reader, file, &handler);
+                    }
+                });
+            }
+        });
+        Ok(Self { path: path.to_path_buf() })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(windows)]
+mod pipe {
+    use std::fs::File;
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::io::FromRawHandle;
+    use std::path::Path;
+    use std::ptr;
+
+    use winapi::shared::winerror::ERROR_PIPE_CONNECTED;
+    use winapi::um::errhandlingapi::GetLastError;
+    use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+    use winapi::um::namedpipeapi::{ConnectNamedPipe, CreateNamedPipeW};
+    use winapi::um::winbase::{
+        FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS,
+        PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+    };
+    use winapi::um::winnt::HANDLE;
+
+    pub struct Pipe(HANDLE);
+
+    // فقط بین thread سرور جابه‌جا می‌شود
+    unsafe impl Send for Pipe {}
+
+    /// `first` یعنی اگر watcher دیگری همین pipe را دارد شکست بخور
+    pub fn create(name: &Path, first: bool) -> io::Result<Pipe> {
+        let wide: Vec<u16> = name.as_os_str().encode_wide().chain(Some(0)).collect();
+        let mut open_mode = PIPE_ACCESS_DUPLEX;
+        if first {
+            open_mode |= FILE_FLAG_FIRST_PIPE_INSTANCE;
+        }
+        let mode = PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS;
+        let h = unsafe {
+            CreateNamedPipeW(wide.as_ptr(), open_mode, mode, PIPE_UNLIMITED_INSTANCES, 4096, 4096, 0, ptr::null_mut())
+        };
+        if h == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Pipe(h))
+    }
+
+    /// تا اتصال یک کلاینت صبر می‌کند
+    pub fn accept(pipe: Pipe) -> io::Result<File> {
+        let ok = unsafe { ConnectNamedPipe(pipe.0, ptr::null_mut()) };
+        if ok == 0 && unsafe { GetLastError() } != ERROR_PIPE_CONNECTED {
+            let e = io::Error::last_os_error();
+            unsafe { CloseHandle(pipe.0) };
+            return Err(e);
+        }
+        Ok(unsafe { File::from_raw_handle(pipe.0 as _) })
+    }
+}
+
+/// یک فرمان به watcher می‌فرستد؛ اگر watcher اجرا نباشد خطای NotFound/ConnectionRefused برمی‌گردد
+pub fn call(command: Command) -> io::Result<Response> {
+    call_at(&endpoint(), command)
+}
+
+pub fn call_at(path: &Path, command: Command) -> io::Result<Response> {
+    let req = serde_json::to_string(&Request { v: VERSION, command })?;
+    let line = exchange(path, &req)?;
+    let resp: Response = serde_json::from_str(&line)?;
+    if resp.v != VERSION {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("watcher speaks protocol version {}", resp.v)));
+    }
+    Ok(resp)
+}
+
+#[cfg(unix)]
+fn exchange(path: &Path, req: &str) -> io::Result<String> {
+    use std::os::unix::net::UnixStream;
+    use std::time::Duration;
+
+    let mut stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(2)))?;
+    writeln!(stream, "{}", req)?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    Ok(line)
+}
+
+#[cfg(windows)]
+fn exchange(path: &Path, req: &str) -> io::Result<String> {
+    use std::fs::OpenOptions;
+    use std::time::Duration;
+
+    const ERROR_PIPE_BUSY: i32 = 231;
+    let mut tries = 0;
+    let mut pipe = loop {
+        match OpenOptions::new().read(true).write(true).open(path) {
+            Ok(f) => break f,
+            // سرور بین دو اتصال است؛ کمی صبر کن
+            Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) && tries < 20 => {
+                tries += 1;
+                thread::sleep(Duration::from_millis(50));
+            }
+            Err(e) => return Err(e),
+        }
+    };
+    writeln!(pipe, "{}", req)?;
+    let mut line = String::new();
+    BufReader::new(pipe).read_line(&mut line)?;
+    Ok(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handler(cmd: Command) -> Response {
+        match cmd {
+            Command::Status => Response::with_status(Status { pid: 7, paused: true, ..Status::default() }),
+            Command::Shutdown => Response::error("not allowed"),
+            _ => Response::ok(),
+        }
+    }
+
+    #[test]
+    fn wire_format() {
+        let req = Request { v: VERSION, command: Command::ApplyNow };
+        assert_eq!(serde_json::to_string(&req).unwrap(), r#"{"v":1,"cmd":"apply_now"}"#);
+        let req = Request { v: VERSION, command: Command::Activate { args: vec!["--settings".into()] } };
+        assert_eq!(serde_json::to_string(&req).unwrap(), r#"{"v":1,"cmd":"activate","args":["--settings"]}"#);
+        let parsed: Request = serde_json::from_str(r#"{"v":1,"cmd":"activate"}"#).unwrap();
+        assert_eq!(parsed.command, Command::Activate { args: vec![] });
+        assert_eq!(serde_json::to_string(&Response::ok()).unwrap(), r#"{"v":1,"ok":true}"#);
+    }
+
+    #[test]
+    fn dispatch_checks_version_and_command() {
+        assert_eq!(dispatch(r#"{"v":1,"cmd":"ping"}"#, &handler), Response::ok());
+        assert_eq!(dispatch(r#"{"v":1,"cmd":"status"}"#, &handler).status.unwrap().pid, 7);
+
+        let old = dispatch(r#"{"v":2,"cmd":"ping"}"#, &handler);
+        assert!(!old.ok);
+        assert!(old.error.unwrap().contains("version 2"));
+        assert!(!dispatch(r#"{"cmd":"ping"}"#, &handler).ok);
+        assert!(!dispatch(r#"{"v":1,"cmd":"format_disk"}"#, &handler).ok);
+        assert!(!dispatch("not json", &handler).ok);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn round_trip_over_socket() {
+        let dir = std::env::temp_dir().join(format!("langswitcher-ipc-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.sock");
+
+        assert!(call_at(&path, Command::Ping).is_err());
+        let server = Server::bind_at(&path, Arc::new(handler)).unwrap();
+        assert_eq!(call_at(&path, Command::Ping).unwrap(), Response::ok());
+        let status = call_at(&path, Command::Status).unwrap().status.unwrap();
+        assert!(status.paused);
+        assert_eq!(call_at(&path, Command::Shutdown).unwrap().error.as_deref(), Some("not allowed"));
+
+        // watcher دوم نباید سوکت اولی را بدزدد
+        assert_eq!(Server::bind_at(&path, Arc::new(handler)).err().map(|e| e.kind()), Some(io::ErrorKind::AddrInUse));
+        drop(server);
+        assert!(!path.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
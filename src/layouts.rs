@@ -0,0 +1,127 @@
+// src/layouts.rs
+// جدول چیدمان‌های کیبورد؛ کاربر می‌تواند از طریق بخش `layouts` کانفیگ آن را گسترش دهد
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Layout {
+    /// تگ BCP-47 که در قوانین استفاده می‌شود ("en", "fa", "en-dvorak", ...)
+    pub tag: String,
+    pub name: String,
+    /// Windows keyboard layout id, e.g. "00000409"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub klid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xkb_layout: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xkb_variant: Option<String>,
+}
+
+// tag, name, KLID, xkb layout, xkb variant
+const BUILTIN: &[(&str, &str, &str, &str, &str)] = &[
+    ("en", "English (US)", "00000409", "us", ""),
+    ("fa", "Persian", "00000429", "ir", ""),
+    ("ar", "Arabic (101)", "00000401", "ara", ""),
+    ("ru", "Russian", "00000419", "ru", ""),
+    ("de", "German", "00000407", "de", ""),
+    ("en-dvorak", "English (Dvorak)", "00010409", "us", "dvorak"),
+    ("fr", "French", "0000040C", "fr", ""),
+    ("es", "Spanish", "0000040A", "es", ""),
+    ("tr", "Turkish (Q)", "0000041F", "tr", ""),
+    ("he", "Hebrew", "0000040D", "il", ""),
+    ("uk", "Ukrainian", "00000422", "ua", ""),
+];
+
+fn non_empty(s: &str) -> Option<String> {
+    if s.is_empty() { None } else { Some(s.to_string()) }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutRegistry {
+    layouts: Vec<Layout>,
+}
+
+impl Default for LayoutRegistry {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
+
+impl LayoutRegistry {
+    /// چیدمان‌های داخلی + چیدمان‌های کاربر؛ تگ تکراری نسخهٔ داخلی را جایگزین می‌کند
+    pub fn new(extra: &[Layout]) -> Self {
+        let mut layouts: Vec<Layout> = BUILTIN
+            .iter()
+            .map(|(tag, name, klid, xkb, variant)| Layout {
+                tag: tag.to_string(),
+                name: name.to_string(),
+                klid: non_empty(klid),
+                xkb_layout: non_empty(xkb),
+                xkb_variant: non_empty(variant),
+            })
+            .collect();
+
+        for l in extra {
+            match layouts.iter_mut().find(|x| x.tag.eq_ignore_ascii_case(&l.tag)) {
+                Some(existing) => *existing = l.clone(),
+                None => layouts.push(l.clone()),
+            }
+        }
+        Self { layouts }
+    }
+
+    pub fn get(&self, tag: &str) -> Option<&Layout> {
+        self.layouts.iter().find(|l| l.tag.eq_ignore_ascii_case(tag))
+    }
+
+    pub fn all(&self) -> &[Layout] {
+        &self.layouts
+    }
+
+    /// برعکس get: از KLID ویندوز به چیدمان
+    pub fn by_klid(&self, klid: &str) -> Option<&Layout> {
+        self.layouts
+            .iter()
+            .find(|l| l.klid.as_deref().is_some_and(|k| k.eq_ignore_ascii_case(klid)))
+    }
+
+    /// از layout/variant در XKB به چیدمان؛ variant خالی یعنی چیدمان پایه
+    pub fn by_xkb(&self, layout: &str, variant: Option<&str>) -> Option<&Layout> {
+        let variant = variant.filter(|v| !v.is_empty());
+        self.layouts
+            .iter()
+            .find(|l| l.xkb_layout.as_deref() == Some(layout) && l.xkb_variant.as_deref() == variant)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_lookups() {
+        let reg = LayoutRegistry::default();
+        assert_eq!(reg.get("FA").and_then(|l| l.klid.as_deref()), Some("00000429"));
+        assert_eq!(reg.by_klid("00010409").map(|l| l.tag.as_str()), Some("en-dvorak"));
+        assert_eq!(reg.by_xkb("us", Some("dvorak")).map(|l| l.tag.as_str()), Some("en-dvorak"));
+        assert_eq!(reg.by_xkb("us", Some("")).map(|l| l.tag.as_str()), Some("en"));
+        assert!(reg.get("xx").is_none());
+    }
+
+    #[test]
+    fn user_layouts_extend_and_override() {
+        let extra = vec![
+            Layout {
+                tag: "ru".into(),
+                name: "Russian (phonetic)".into(),
+                klid: Some("00020419".into()),
+                xkb_layout: Some("ru".into()),
+                xkb_variant: Some("phonetic".into()),
+            },
+            Layout { tag: "ku".into(), name: "Kurdish".into(), klid: None, xkb_layout: Some("iq".into()), xkb_variant: Some("ku".into()) },
+        ];
+        let reg = LayoutRegistry::new(&extra);
+        assert_eq!(reg.get("ru").map(|l| l.name.as_str()), Some("Russian (phonetic)"));
+        assert_eq!(reg.get("ku").and_then(|l| l.xkb_layout.as_deref()), Some("iq"));
+        assert_eq!(reg.all().iter().filter(|l| l.tag == "ru").count(), 1);
+    }
+}
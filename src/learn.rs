@@ -0,0 +1,166 @@
+// src/learn.rs
+// حالت یادگیری: تعویض دستی چیدمان داخل یک برنامه را می‌بیند و بعد از چند بار تکرار آن را ترجیح برنامه می‌داند.
+//
+// هر تعویض دستی که حداقل `learn_debounce_ms` بماند یک رأی است. رأی برای چیدمان دیگر شمارنده را صفر می‌کند
+// و با رسیدن به `learn_confidence` رأیِ پشت سر هم، چیدمان برای ثبت در `learned` برگردانده می‌شود.
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::config::Settings;
+
+#[derive(Debug)]
+struct Session {
+    app: String,
+    /// آخرین چیدمانی که در این برنامه دیده شد
+    layout: Option<String>,
+    /// چیدمانی که خود watcher درخواست داده؛ رأی به آن معنایی ندارد
+    applied: Option<String>,
+    /// زمان آخرین تعویض دستی که هنوز رأی نشده
+    changed_at: Option<Instant>,
+}
+
+#[derive(Debug, Default)]
+pub struct Learner {
+    session: Option<Session>,
+    /// نام برنامه با حروف کوچک -> (چیدمان، تعداد رأی)
+    votes: HashMap<String, (String, u32)>,
+}
+
+impl Learner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// watcher خودش چیدمان را عوض کرد؛ این تغییر دستی حساب نمی‌شود
+    pub fn expect(&mut self, app: &str, lang: &str) {
+        self.session = Some(Session {
+            app: app.to_string(),
+            layout: Some(lang.to_string()),
+            applied: Some(lang.to_string()),
+            changed_at: None,
+        });
+    }
+
+    /// با هر دور watcher صدا زده می‌شود؛ Some(lang) یعنی این برنامه را باید با lang ثبت کرد
+    pub fn observe(&mut self, app: &str, layout: Option<&str>, now: Instant, settings: &Settings) -> Option<String> {
+        let session = match &mut self.session {
+            Some(s) if s.app.eq_ignore_ascii_case(app) => s,
+            _ => {
+                // برنامهٔ تازه: چیدمان فعلی فقط مبناست، رأی نیست
+                self.session = Some(Session {
+                    app: app.to_string(),
+                    layout: layout.map(str::to_string),
+                    applied: None,
+                    changed_at: None,
+                });
+                return None;
+            }
+        };
+
+        if session.layout.as_deref() != layout {
+            session.layout = layout.map(str::to_string);
+            session.changed_at = layout.map(|_| now);
+            return None;
+        }
+
+        let since = session.changed_at?;
+        if now.duration_since(since) < Duration::from_millis(settings.learn_debounce_ms) {
+            return None;
+        }
+        session.changed_at = None;
+        let lang = session.layout.clone()?;
+        if session.applied.as_deref() == Some(lang.as_str()) {
+            // کاربر دوباره به همان چیدمان قانون برگشت
+            self.votes.remove(&app.to_lowercase());
+            return None;
+        }
+
+        let key = app.to_lowercase();
+        let entry = self.votes.entry(key.clone()).or_insert_with(|| (lang.clone(), 0));
+        if entry.0 != lang {
+            *entry = (lang.clone(), 0);
+        }
+        entry.1 += 1;
+        if entry.1 < settings.learn_confidence {
+            return None;
+        }
+        self.votes.remove(&key);
+        Some(lang)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> Settings {
+        Settings { learning: true, learn_debounce_ms: 1000, learn_confidence: 2, ..Settings::default() }
+    }
+
+    /// یک تعویض دستی به `lang` که `hold` میلی‌ثانیه می‌ماند
+    fn switch(l: &mut Learner, app: &str, lang: &str, t: &mut Instant, hold: u64) -> Option<String> {
+        let s = settings();
+        assert_eq!(l.observe(app, Some(lang), *t, &s), None);
+        *t += Duration::from_millis(hold);
+        l.observe(app, Some(lang), *t, &s)
+    }
+
+    #[test]
+    fn repeated_manual_switches_are_learned() {
+        let mut l = Learner::new();
+        let mut t = Instant::now();
+        let s = settings();
+        assert_eq!(l.observe("Telegram.exe", Some("en"), t, &s), None);
+        assert_eq!(switch(&mut l, "Telegram.exe", "fa", &mut t, 1500), None);
+
+        // برنامهٔ دیگر و برگشت؛ رأی قبلی می‌ماند
+        assert_eq!(l.observe("Code.exe", Some("en"), t, &s), None);
+        assert_eq!(l.observe("telegram.exe", Some("en"), t, &s), None);
+        assert_eq!(switch(&mut l, "telegram.exe", "fa", &mut t, 1500).as_deref(), Some("fa"));
+    }
+
+    #[test]
+    fn short_lived_switch_is_not_a_vote() {
+        let mut l = Learner::new();
+        let mut t = Instant::now();
+        let s = settings();
+        l.observe("Telegram.exe", Some("en"), t, &s);
+        for _ in 0..5 {
+            assert_eq!(l.observe("Telegram.exe", Some("fa"), t, &s), None);
+            t += Duration::from_millis(200);
+            assert_eq!(l.observe("Telegram.exe", Some("en"), t, &s), None);
+        }
+        t += Duration::from_millis(1500);
+        // برگشت به چیدمان مبنا هم رأی است، ولی یک رأی کافی نیست
+        assert_eq!(l.observe("Telegram.exe", Some("en"), t, &s), None);
+    }
+
+    #[test]
+    fn conflicting_vote_resets_confidence() {
+        let mut l = Learner::new();
+        let mut t = Instant::now();
+        l.observe("app", Some("en"), t, &settings());
+        assert_eq!(switch(&mut l, "app", "fa", &mut t, 1500), None);
+        assert_eq!(switch(&mut l, "app", "ru", &mut t, 1500), None);
+        assert_eq!(switch(&mut l, "app", "fa", &mut t, 1500), None);
+        assert_eq!(switch(&mut l, "app", "en", &mut t, 1500), None);
+        assert_eq!(switch(&mut l, "app", "fa", &mut t, 1500), None);
+        assert_eq!(switch(&mut l, "app", "en", &mut t, 1500), None);
+        assert_eq!(switch(&mut l, "app", "en", &mut t, 1500), None);
+    }
+
+    #[test]
+    fn watcher_switches_are_not_learned() {
+        let mut l = Learner::new();
+        let mut t = Instant::now();
+        let s = settings();
+        for _ in 0..3 {
+            l.observe("Code.exe", Some("fa"), t, &s);
+            l.expect("Code.exe", "en");
+            t += Duration::from_millis(1500);
+            assert_eq!(l.observe("Code.exe", Some("en"), t, &s), None);
+            l.observe("chrome.exe", Some("en"), t, &s);
+        }
+        assert!(l.votes.is_empty());
+    }
+}
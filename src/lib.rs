@@ -0,0 +1,14 @@
+// src/lib.rs
+// منطق مشترک بین GUI و watcher
+pub mod backend;
+pub mod cli;
+pub mod config;
+pub mod core;
+pub mod instance;
+pub mod ipc;
+pub mod layouts;
+pub mod learn;
+pub mod monitor;
+pub mod rules;
+pub mod switch;
+pub mod watch;
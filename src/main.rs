@@ -1,35 +1,47 @@
 // src/main.rs
 #![windows_subsystem = "windows"]
 
-mod core;
-use core::LangState;
+use lang_switcher_rust::config::{ConfigError, UnmappedPolicy};
+use lang_switcher_rust::core::{LangState, ProgramEvent};
+use lang_switcher_rust::instance;
+use lang_switcher_rust::ipc;
+use lang_switcher_rust::layouts::LayoutRegistry;
+use lang_switcher_rust::monitor::WatcherMonitor;
 
 use eframe::egui;
 use egui::{ColorImage, TextureHandle, RichText};
-use image;
-use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::process::{Child, Command};
-
-use sysinfo::{ProcessExt, System, SystemExt};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use std::os::windows::process::CommandExt; // فقط ویندوز
-use std::sync::Arc;
 
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
 const ICON_FOLDER: &str = "icons";
 const DEFAULT_ICON_NAME: &str = "default.png";
 
+/// خطای ذخیره در پنجره هم دیده شود؛ باینری ویندوز کنسول ندارد. ذخیرهٔ موفق پیام قبلی را پاک می‌کند
+fn report(slot: &mut Option<String>, what: &str, res: Result<(), ConfigError>) {
+    match res {
+        Ok(()) => *slot = None,
+        Err(e) => {
+            eprintln!("{}: {}", what, e);
+            *slot = Some(format!("{}: {}", what, e));
+        }
+    }
+}
+
 /// بارگذاری تکسچر آیکون — مسیرها نسبت به مسیر فایل اجرایی مشخص می‌شوند
 fn load_icon_texture(ctx: &egui::Context, texture_id: &str, prog_name: &str) -> Option<TextureHandle> {
     // map exe -> pre-bundled png
-    let map = vec![
-        ("chrome.exe", "chrome.png"),
+    let map = [("chrome.exe", "chrome.png"),
         ("firefox.exe", "firefox.png"),
         ("Code.exe", "code.png"),
         ("PyCharm.exe", "pycharm.png"),
-        ("Opera.exe", "opera.png"),
-    ];
+        ("Opera.exe", "opera.png")];
 
     // base dir = executable directory (important for shortcuts/installers)
     let exe_base = std::env::current_exe().ok().and_then(|p| p.parent().map(|pp| pp.to_path_buf()))
@@ -101,6 +113,23 @@ fn draw_toggle(ui: &mut egui::Ui, on: &mut bool) -> egui::Response {
     resp
 }
 
+/// رنگ حاشیهٔ کارت و برچسب زبان؛ هر چیدمان رنگ ثابت خودش را از روی جایگاهش در جدول می‌گیرد.
+/// برنامهٔ بدون قانون خاکستری است
+fn lang_colors(registry: &LayoutRegistry, lang: Option<&str>) -> (egui::Color32, egui::Color32) {
+    use egui::Color32 as C;
+    let Some(lang) = lang else { return (C::from_gray(70), C::from_gray(120)) };
+    const PALETTE: [(C, C); 6] = [
+        (C::from_rgb(100, 150, 255), C::from_rgb(50, 110, 190)),
+        (C::from_rgb(80, 220, 140), C::from_rgb(40, 150, 70)),
+        (C::from_rgb(255, 170, 80), C::from_rgb(190, 110, 30)),
+        (C::from_rgb(240, 100, 160), C::from_rgb(170, 50, 110)),
+        (C::from_rgb(90, 220, 230), C::from_rgb(30, 140, 160)),
+        (C::from_rgb(200, 200, 110), C::from_rgb(140, 140, 50)),
+    ];
+    let idx = registry.all().iter().position(|l| l.tag.eq_ignore_ascii_case(lang)).unwrap_or(0);
+    PALETTE[idx % PALETTE.len()]
+}
+
 fn apply_cyberpunk_theme(ctx: &egui::Context) {
     let mut style = (*ctx.style()).clone();
     style.visuals.dark_mode = true;
@@ -120,9 +149,15 @@ fn apply_cyberpunk_theme(ctx: &egui::Context) {
 
 struct LangApp {
     state: LangState,
-    textures: Vec<Option<TextureHandle>>,
-    watcher: Option<Child>,   // اگر خود اپ spawn کرد نگه داشته میشه
-    watcher_enabled: bool,    // نشان‌دهنده وضعیت دکمه (on/off)
+    /// آیکون هر برنامه به اسمش؛ با جابه‌جا شدن ردیف‌ها دوباره خوانده نمی‌شود
+    textures: HashMap<String, Option<TextureHandle>>,
+    watcher: Option<Child>,   // اگر خود اپ spawn کرد نگه داشته میشه (تا وقتی زنده است)
+    /// آخرین خطای ذخیرهٔ کانفیگ، زیر نوار ابزار نشان داده می‌شود
+    save_error: Option<String>,
+    /// وضعیت watcher که در پس‌زمینه تازه می‌شود؛ رندر فقط همین را می‌خواند
+    monitor: WatcherMonitor,
+    /// نسخهٔ دوم GUI از این راه پنجرهٔ ما را جلو می‌آورد
+    _server: Option<ipc::Server>,
 }
 
 impl LangApp {
@@ -130,29 +165,77 @@ impl LangApp {
         apply_cyberpunk_theme(&cc.egui_ctx);
 
         let st = LangState::new();
-        let textures = st.programs.iter().enumerate().map(|(i, p)| {
-            let tid = format!("icon-{}", i);
-            load_icon_texture(&cc.egui_ctx, &tid, &p.name)
+        let textures = st.programs.iter().map(|p| {
+            (p.name.clone(), load_icon_texture(&cc.egui_ctx, &format!("icon-{}", p.name), &p.name))
         }).collect();
 
         // default: watcher should NOT auto-start.
-        // اگر watcher قبلاً توسط کاربر یا تری اجرا شده بود، دکمه را روشن کن (ولی spawn نکن)
-        let mut app = Self { state: st, textures, watcher: None, watcher_enabled: false };
-        if app.is_watcher_running() {
-            app.watcher_enabled = true;
+        // وضعیت دکمه هر فریم از monitor خوانده می‌شود (ولی spawn نمی‌شود)
+        let repaint = cc.egui_ctx.clone();
+        let monitor = WatcherMonitor::start(Duration::from_secs(1), move || repaint.request_repaint());
+
+        let ctx = cc.egui_ctx.clone();
+        let handler: ipc::Handler = Arc::new(move |cmd| match cmd {
+            ipc::Command::Ping => ipc::Response::ok(),
+            ipc::Command::Activate { .. } => {
+                ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+                ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+                ctx.request_repaint();
+                ipc::Response::ok()
+            }
+            _ => ipc::Response::error("not supported by the settings window"),
+        });
+        let server = ipc::Server::bind_at(&ipc::endpoint_for(ipc::GUI), handler)
+            .map_err(|e| eprintln!("GUI IPC endpoint unavailable: {}", e))
+            .ok();
+
+        Self {
+            state: st,
+            textures,
+            watcher: None,
+            save_error: None,
+            monitor,
+            _server: server,
         }
+    }
 
-        app
+    /// فقط آیکون ردیف‌های تازه خوانده می‌شود؛ آیکون برنامه‌ای که دیگر ردیفی ندارد رها می‌شود
+    fn update_textures(&mut self, ctx: &egui::Context, events: &[ProgramEvent]) {
+        for e in events {
+            if let ProgramEvent::Added(p) | ProgramEvent::Changed(p) = e {
+                self.textures
+                    .entry(p.name.clone())
+                    .or_insert_with(|| load_icon_texture(ctx, &format!("icon-{}", p.name), &p.name));
+            }
+        }
+        self.prune_textures();
     }
 
-    /// آیا watcher در سیستم (حتی اگر توسط ما spawn نشده) در حال اجراست؟
+    fn prune_textures(&mut self) {
+        let programs = &self.state.programs;
+        self.textures.retain(|name, _| programs.iter().any(|p| &p.name == name));
+    }
+
+    /// آیا watcher (حتی اگر توسط ما spawn نشده) جواب IPC می‌دهد؟ فقط کش را می‌خواند
     fn is_watcher_running(&self) -> bool {
-        let mut sys = System::new_all();
-        sys.refresh_processes();
-        sys.processes().values().any(|p| {
-            let name = p.name();
-            name.eq_ignore_ascii_case("watcher.exe") || name.eq_ignore_ascii_case("watcher")
-        })
+        self.monitor.is_running()
+    }
+
+    /// watcher که خودمان ساخته‌ایم و خارج شده (shutdown، Quit از تری یا کرش) دیگر «starting» نیست
+    fn reap_watcher(&mut self) {
+        let Some(child) = &mut self.watcher else { return };
+        match child.try_wait() {
+            Ok(None) => {}
+            Ok(Some(status)) => {
+                println!("spawned watcher exited ({}).", status);
+                self.watcher = None;
+                self.monitor.refresh_now();
+            }
+            Err(e) => {
+                eprintln!("could not check spawned watcher: {}", e);
+                self.watcher = None;
+            }
+        }
     }
 
     /// spawn watcher (فقط وقتی که لازم باشه و ما مسئول اون هستیم)
@@ -174,9 +257,9 @@ impl LangApp {
             if let Some(parent) = exe_path.parent() {
                 let watcher_name = if cfg!(windows) { "watcher.exe" } else { "watcher" };
                 let candidates = vec![
-                    parent.join(&watcher_name),
-                    parent.join("target").join("release").join(&watcher_name),
-                    parent.join("target").join("debug").join(&watcher_name),
+                    parent.join(watcher_name),
+                    parent.join("target").join("release").join(watcher_name),
+                    parent.join("target").join("debug").join(watcher_name),
                 ];
 
                 for cand in candidates {
@@ -187,6 +270,7 @@ impl LangApp {
                             .spawn() {
                             Ok(child) => {
                                 self.watcher = Some(child);
+                                self.monitor.refresh_now();
                                 println!("Watcher started (spawned by GUI).");
                                 return;
                             }
@@ -207,31 +291,46 @@ impl LangApp {
         eprintln!("No watcher binary started.");
     }
 
-    /// stop watcher: اگر ما spawn کرده بودیم kill کنیم، در غیر این صورت تلاش best-effort برای taskkill
+    /// stop watcher: از طریق IPC خاموشش کن؛ kill فقط برای پروسه‌ای که خودمان ساخته‌ایم و جواب نمی‌دهد
     fn stop_watcher(&mut self) {
+        let asked = match ipc::call(ipc::Command::Shutdown) {
+            Ok(r) if r.ok => true,
+            Ok(r) => {
+                eprintln!("watcher refused shutdown: {}", r.error.unwrap_or_default());
+                false
+            }
+            Err(e) => {
+                println!("watcher not reachable over IPC: {}", e);
+                false
+            }
+        };
+
         if let Some(mut child) = self.watcher.take() {
+            // چند لحظه فرصت خروج تمیز (پاک کردن سوکت و ...)
+            let deadline = Instant::now() + Duration::from_secs(2);
+            while asked && Instant::now() < deadline {
+                if let Ok(Some(_)) = child.try_wait() {
+                    println!("spawned watcher exited.");
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            }
             if let Err(e) = child.kill() { eprintln!("failed to kill spawned watcher: {}", e); }
             let _ = child.wait();
             println!("killed spawned watcher (we created it).");
-            return;
-        }
-
-        // اگر watcher توسط خارجی اجرا شده بود، سعی کن با taskkill/ pkill ببندی
-        if self.is_watcher_running() {
-            if cfg!(windows) {
-                let _ = Command::new("taskkill").args(&["/IM", "watcher.exe", "/F"]).spawn();
-            } else {
-                let _ = Command::new("pkill").arg("-f").arg("watcher").spawn();
-            }
-            println!("attempted to kill watcher processes by name (best-effort).");
-        } else {
-            println!("no watcher process found to kill.");
         }
+        self.monitor.refresh_now();
     }
 }
 
 impl eframe::App for LangApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.reap_watcher();
+        if self.watcher.is_some() {
+            // خروج پروسه رویداد egui نیست؛ بدون این برچسب تا حرکت بعدی موس «starting» می‌ماند
+            ctx.request_repaint_after(Duration::from_secs(1));
+        }
+
         // bottom panel: watcher toggle
         egui::TopBottomPanel::bottom("watcher_panel").resizable(false).min_height(70.0).show(ctx, |ui| {
             ui.add_space(6.0);
@@ -240,15 +339,13 @@ impl eframe::App for LangApp {
             ui.horizontal_centered(|ui| {
                 egui::Frame::none().inner_margin(egui::style::Margin::same(8.0)).show(ui, |ui| {
                     ui.horizontal(|ui| {
-                        // local toggle reflects watcher_enabled
-                        let mut local_on = self.watcher_enabled;
+                        // دکمه همان چیزی را نشان می‌دهد که برچسب وضعیت نشان می‌دهد
+                        let mut local_on = self.is_watcher_running() || self.watcher.is_some();
                         let resp = draw_toggle(ui, &mut local_on);
 
                         // وقتی کلیک شد:
                         if resp.clicked() {
-                            // apply change to state
-                            self.watcher_enabled = local_on;
-                            if self.watcher_enabled {
+                            if local_on {
                                 // فقط اگر غیرفعال بود، اجرا کن
                                 if !self.is_watcher_running() {
                                     self.start_watcher();
@@ -262,10 +359,19 @@ impl eframe::App for LangApp {
                         }
 
                         ui.add_space(10.0);
-                        let running = self.is_watcher_running() || self.watcher.is_some();
+                        let status = self.monitor.status();
+                        let state = match &status {
+                            Some(s) if s.paused => "paused",
+                            Some(_) => "running",
+                            None if self.watcher.is_some() => "starting",
+                            None => "stopped",
+                        };
                         ui.vertical(|ui| {
                             ui.label(RichText::new("Watcher").strong().color(egui::Color32::from_rgb(190,170,255)));
-                            ui.label(RichText::new(if running { "running" } else { "stopped" }).small());
+                            ui.label(RichText::new(state).small());
+                            if let Some(e) = status.as_ref().and_then(|s| s.events.last()) {
+                                ui.label(RichText::new(e.to_string()).small().color(egui::Color32::from_gray(150)));
+                            }
                         });
 
                         ui.add_space(24.0);
@@ -295,39 +401,114 @@ impl eframe::App for LangApp {
 
             ui.horizontal(|ui| {
                 if ui.button("Refresh (scan processes)").clicked() {
-                    self.state.refresh();
-                    self.textures = self.state.programs.iter().enumerate().map(|(i, p)| {
-                        let tid = format!("icon-{}", i);
-                        load_icon_texture(ctx, &tid, &p.name)
-                    }).collect();
+                    let events = self.state.refresh();
+                    self.update_textures(ctx, &events);
+                }
+                let mut learning = self.state.learning;
+                if ui.checkbox(&mut learning, "Learn from manual switches")
+                    .on_hover_text("the watcher remembers the layout you keep picking by hand in each program")
+                    .changed()
+                {
+                    report(&mut self.save_error, "Save error", self.state.set_learning(learning));
+                }
+                let mut per_window = self.state.per_window;
+                if ui.checkbox(&mut per_window, "Per window")
+                    .on_hover_text("restore the layout each window had when you come back to it")
+                    .changed()
+                {
+                    report(&mut self.save_error, "Save error", self.state.set_per_window(per_window));
+                }
+
+                // برنامه‌های بدون قانون؛ فقط وقتی کاربر واقعاً چیزی انتخاب کرد ذخیره می‌شود،
+                // وگرنه ذخیرهٔ ناموفق هر فریم دوباره امتحان می‌شد
+                let mut policy = self.state.unmapped;
+                let mut default_lang = self.state.default_lang.clone();
+                let mut picked = false;
+                ui.label("Unmapped apps:");
+                egui::ComboBox::from_id_source("unmapped_policy")
+                    .selected_text(policy.label())
+                    .width(80.0)
+                    .show_ui(ui, |ui| {
+                        for p in UnmappedPolicy::ALL {
+                            picked |= ui.selectable_value(&mut policy, p, p.label()).changed();
+                        }
+                    })
+                    .response
+                    .on_hover_text("leave: keep the previous layout\ndefault: switch to the default layout\nrestore: go back to the layout you had before a rule switched it");
+                if policy == UnmappedPolicy::Default {
+                    let registry = &self.state.registry;
+                    egui::ComboBox::from_id_source("default_lang")
+                        .selected_text(registry.get(&default_lang).map(|l| l.name.clone()).unwrap_or_else(|| default_lang.clone()))
+                        .width(120.0)
+                        .show_ui(ui, |ui| {
+                            for layout in registry.all() {
+                                picked |= ui.selectable_value(&mut default_lang, layout.tag.clone(), &layout.name).changed();
+                            }
+                        });
                 }
-                if ui.button("Save Now").clicked() {
-                    if let Err(e) = self.state.save_config() { ui.label(format!("Save error: {}", e)); } else { ui.label("Saved."); }
+                if picked {
+                    report(&mut self.save_error, "Save error", self.state.set_unmapped(policy, &default_lang));
                 }
 
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                    ui.label(RichText::new("toggle each program to set language").small().color(egui::Color32::from_gray(160)));
+                    ui.label(RichText::new("pick a keyboard layout for each program").small().color(egui::Color32::from_gray(160)));
                 });
             });
+            if let Some(err) = &self.save_error {
+                ui.colored_label(egui::Color32::from_rgb(255, 110, 110), err);
+            }
 
             ui.separator();
+
+            if !self.state.learned.is_empty() {
+                let mut promote: Option<String> = None;
+                let mut forget: Option<String> = None;
+                egui::CollapsingHeader::new(format!("Learned layouts ({})", self.state.learned.len()))
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        for l in &self.state.learned {
+                            ui.horizontal(|ui| {
+                                let (_, pill_color) = lang_colors(&self.state.registry, Some(&l.lang));
+                                ui.label(RichText::new(&l.process).strong());
+                                ui.colored_label(pill_color, l.lang.to_uppercase());
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    if ui.small_button("Forget").clicked() {
+                                        forget = Some(l.process.clone());
+                                    }
+                                    if ui.small_button("Promote").on_hover_text("turn this into a regular rule").clicked() {
+                                        promote = Some(l.process.clone());
+                                    }
+                                });
+                            });
+                        }
+                    });
+                if let Some(name) = promote {
+                    report(&mut self.save_error, "Promote error", self.state.promote_learned(&name));
+                }
+                if let Some(name) = forget {
+                    report(&mut self.save_error, "Forget error", self.state.forget_learned(&name));
+                }
+                ui.separator();
+            }
+
             ui.add_space(8.0);
 
             egui::ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
-                let mut changed_any = false;
-                for (idx, prog) in self.state.programs.iter_mut().enumerate() {
+                let mut changed: Option<(String, String)> = None;
+                let mut remove: Option<String> = None;
+                for (idx, prog) in self.state.programs.iter().enumerate() {
                     ui.add_space(6.0);
                     let available_width = ui.available_width();
                     let card_size = egui::vec2(available_width, 72.0);
                     let (card_rect, card_resp) = ui.allocate_exact_size(card_size, egui::Sense::hover());
-                    let border_color = if prog.lang == "en" { egui::Color32::from_rgb(100,150,255) } else { egui::Color32::from_rgb(80,220,140) };
-                    let card_bg = egui::Color32::from_rgb(10,8,22);
+                    let (border_color, pill_color) = lang_colors(&self.state.registry, prog.lang.as_deref());
+                    let card_bg = if prog.running { egui::Color32::from_rgb(10,8,22) } else { egui::Color32::from_rgb(16,14,24) };
 
                     ui.painter().rect(card_rect.shrink(2.0), 8.0, card_bg, egui::Stroke::new(if card_resp.hovered() { 2.6 } else { 1.2 }, border_color));
 
                     let mut content_ui = ui.child_ui(card_rect.shrink2(egui::Vec2::splat(8.0)), egui::Layout::left_to_right(egui::Align::Center));
                     content_ui.horizontal(|ui| {
-                        let tex_opt = self.textures.get(idx).and_then(|t| t.as_ref());
+                        let tex_opt = self.textures.get(&prog.name).and_then(|t| t.as_ref());
                         if let Some(tex) = tex_opt {
                             ui.add(egui::Image::new((tex.id(), egui::vec2(48.0, 48.0))));
                         } else {
@@ -341,31 +522,58 @@ impl eframe::App for LangApp {
 
                         ui.vertical(|ui| {
                             ui.label(RichText::new(&prog.name).size(15.0).strong());
-                            ui.label(RichText::new("Click toggle to set language").small().color(egui::Color32::from_gray(140)));
+                            if prog.running && prog.lang.is_none() {
+                                let hint = format!("no rule — unmapped apps: {}", self.state.unmapped.label());
+                                ui.label(RichText::new(hint).small().color(egui::Color32::from_gray(140)));
+                            } else if prog.running {
+                                ui.label(RichText::new("Pick a layout for this program").small().color(egui::Color32::from_gray(140)));
+                            } else {
+                                ui.label(RichText::new("not running").small().italics().color(egui::Color32::from_rgb(200,160,90)));
+                            }
                         });
 
                         ui.add_space(8.0);
 
                         ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                            let pill = if prog.lang == "en" { "EN" } else { "FA" };
-                            let pill_color = if prog.lang == "en" { egui::Color32::from_rgb(50,110,190) } else { egui::Color32::from_rgb(40,150,70) };
-                            ui.colored_label(pill_color, pill);
+                            if !prog.running && ui.small_button("Remove").on_hover_text("delete this rule from the config").clicked() {
+                                remove = Some(prog.name.clone());
+                            }
+                            ui.add_space(8.0);
+                            ui.colored_label(pill_color, prog.lang.as_deref().map(str::to_uppercase).unwrap_or_else(|| "—".to_string()));
                             ui.add_space(8.0);
 
-                            let mut local_on = prog.lang == "en";
-                            let resp = draw_toggle(ui, &mut local_on);
-                            if resp.clicked() || resp.double_clicked() {
-                                prog.lang = if local_on { "en".to_string() } else { "fa".to_string() };
-                                changed_any = true;
-                            }
+                            let registry = &self.state.registry;
+                            let selected = match &prog.lang {
+                                Some(lang) => registry.get(lang).map(|l| l.name.clone()).unwrap_or_else(|| lang.clone()),
+                                None => "No rule".to_string(),
+                            };
+                            egui::ComboBox::from_id_source(("layout", idx))
+                                .selected_text(selected)
+                                .width(150.0)
+                                .show_ui(ui, |ui| {
+                                    // ردیف فقط بعد از ذخیرهٔ موفق عوض می‌شود (LangState::set_rule / remove_rule)
+                                    let mut picked = prog.lang.clone();
+                                    if ui.selectable_value(&mut picked, None, "No rule").changed() {
+                                        remove = Some(prog.name.clone());
+                                    }
+                                    for layout in registry.all() {
+                                        if ui.selectable_value(&mut picked, Some(layout.tag.clone()), &layout.name).changed() {
+                                            changed = Some((prog.name.clone(), layout.tag.clone()));
+                                        }
+                                    }
+                                });
                         });
                     });
 
                     ui.add_space(6.0);
                 }
 
-                if changed_any {
-                    if let Err(e) = self.state.save_config() { eprintln!("Save error: {}", e); }
+                if let Some((name, lang)) = changed {
+                    report(&mut self.save_error, "Save error", self.state.set_rule(&name, &lang));
+                }
+                if let Some(name) = remove {
+                    report(&mut self.save_error, "Remove error", self.state.remove_rule(&name));
+                    self.prune_textures();
                 }
             });
         });
@@ -376,6 +584,22 @@ fn main() {
     // logger برای debug/ info
     env_logger::init();
 
+    // فقط یک پنجرهٔ تنظیمات؛ نسخهٔ دوم پنجرهٔ اول را جلو می‌آورد و خارج می‌شود
+    let _instance = match instance::acquire("gui") {
+        Ok(Some(lock)) => Some(lock),
+        Ok(None) => {
+            let args = std::env::args().skip(1).collect();
+            if let Err(e) = ipc::call_at(&ipc::endpoint_for(ipc::GUI), ipc::Command::Activate { args }) {
+                eprintln!("Settings window already open but not reachable: {}", e);
+            }
+            return;
+        }
+        Err(e) => {
+            eprintln!("Could not take the single-instance lock: {}", e);
+            None
+        }
+    };
+
     let native_options = eframe::NativeOptions::default();
 
     let _ = eframe::run_native(
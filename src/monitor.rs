@@ -0,0 +1,128 @@
+// src/monitor.rs
+// وضعیت watcher برای GUI در یک thread پس‌زمینه؛ رندر فقط نسخهٔ کش‌شده را می‌خواند و هیچ‌وقت منتظر IPC نمی‌ماند.
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::ipc;
+
+pub struct WatcherMonitor {
+    status: Arc<Mutex<Option<ipc::Status>>>,
+    /// بیدار کردن thread قبل از موعد؛ drop شدنش یعنی thread تمام شود
+    wake: Sender<()>,
+}
+
+impl WatcherMonitor {
+    /// هر `interval` از طریق IPC می‌پرسد؛ `on_change` وقتی صدا زده می‌شود که وضعیت عوض شود (مثلاً repaint)
+    pub fn start(interval: Duration, on_change: impl Fn() + Send + 'static) -> Self {
+        Self::start_with(interval, || ipc::call(ipc::Command::Status).ok().and_then(|r| r.status), on_change)
+    }
+
+    /// مثل `start` ولی با تابع دلخواه به جای IPC؛ اولین پرسش همین‌جا انجام می‌شود تا وضعیت اولیه معلوم باشد
+    pub fn start_with(
+        interval: Duration,
+        mut probe: impl FnMut() -> Option<ipc::Status> + Send + 'static,
+        on_change: impl Fn() + Send + 'static,
+    ) -> Self {
+        let status = Arc::new(Mutex::new(probe()));
+        let (wake, rx) = mpsc::channel();
+        let shared = Arc::clone(&status);
+        thread::spawn(move || loop {
+            match rx.recv_timeout(interval) {
+                Ok(()) | Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+            let next = probe();
+            let mut cur = shared.lock().unwrap();
+            if *cur != next {
+                *cur = next;
+                drop(cur);
+                on_change();
+            }
+        });
+        Self { status, wake }
+    }
+
+    /// آخرین وضعیت دیده‌شده؛ None یعنی watcher جواب نداد
+    pub fn status(&self) -> Option<ipc::Status> {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.status.lock().unwrap().is_some()
+    }
+
+    /// بعد از روشن/خاموش کردن watcher، بدون انتظار برای دور بعد دوباره بپرس
+    pub fn refresh_now(&self) {
+        let _ = self.wake.send(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    fn wait_for(cond: impl Fn() -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if cond() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        false
+    }
+
+    #[test]
+    fn reads_are_cached_and_refresh_is_on_demand() {
+        let up = Arc::new(AtomicBool::new(false));
+        let probes = Arc::new(AtomicUsize::new(0));
+        let changes = Arc::new(AtomicUsize::new(0));
+        let m = {
+            let (up, probes, changes) = (Arc::clone(&up), Arc::clone(&probes), Arc::clone(&changes));
+            WatcherMonitor::start_with(
+                Duration::from_secs(3600),
+                move || {
+                    probes.fetch_add(1, Ordering::SeqCst);
+                    up.load(Ordering::SeqCst).then(|| ipc::Status { pid: 42, ..ipc::Status::default() })
+                },
+                move || {
+                    changes.fetch_add(1, Ordering::SeqCst);
+                },
+            )
+        };
+        assert!(!m.is_running());
+        for _ in 0..100 {
+            m.status();
+        }
+        assert_eq!(probes.load(Ordering::SeqCst), 1);
+
+        up.store(true, Ordering::SeqCst);
+        m.refresh_now();
+        assert!(wait_for(|| changes.load(Ordering::SeqCst) == 1));
+        assert_eq!(m.status().unwrap().pid, 42);
+
+        // بدون تغییر، on_change دوباره صدا زده نمی‌شود
+        m.refresh_now();
+        assert!(wait_for(|| probes.load(Ordering::SeqCst) == 3));
+        assert_eq!(changes.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn interval_polls_in_background() {
+        let probes = Arc::new(AtomicUsize::new(0));
+        let p = Arc::clone(&probes);
+        let _m = WatcherMonitor::start_with(
+            Duration::from_millis(10),
+            move || {
+                p.fetch_add(1, Ordering::SeqCst);
+                None
+            },
+            || {},
+        );
+        assert!(wait_for(|| probes.load(Ordering::SeqCst) >= 3));
+    }
+}
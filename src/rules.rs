@@ -0,0 +1,299 @@
+// src/rules.rs
+// تطبیق قوانین کانفیگ با پروسهٔ فعال.
+//
+// اولویت (از قوی به ضعیف): عنوان پنجره، مسیر کامل exe، glob مسیر، regex آرگومان‌ها، نام پروسه، regex نام.
+// قانون عنوان معمولاً همراه process می‌آید (تب GitHub در chrome.exe) و اگر عنوان نخورد، قانون خود پروسه اعمال می‌شود.
+// قانونی که چند شرط دارد با قوی‌ترین شرطش رتبه می‌گیرد؛ در رتبهٔ برابر، قانون جلوتر در فایل برنده است.
+use std::path::Path;
+
+use glob::{MatchOptions, Pattern};
+use regex::{Regex, RegexBuilder};
+
+use crate::config::Rule;
+
+/// چیزهایی که دربارهٔ پروسهٔ فعال می‌دانیم
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessFacts<'a> {
+    pub name: &'a str,
+    pub exe_path: Option<&'a Path>,
+    pub args: &'a [String],
+    /// عنوان پنجرهٔ فعال، اگر backend آن را بدهد
+    pub title: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Rank {
+    Title,
+    ExePath,
+    PathGlob,
+    Args,
+    Process,
+    NameRegex,
+}
+
+#[derive(Debug)]
+struct CompiledRule {
+    index: usize,
+    rank: Rank,
+    process: Option<String>,
+    exe_path: Option<String>,
+    path_glob: Option<Pattern>,
+    args: Option<Regex>,
+    name: Option<Regex>,
+    /// با حروف کوچک، برای مقایسهٔ بدون حساسیت به حروف
+    title: Option<String>,
+    title_regex: Option<Regex>,
+}
+
+/// ویندوز به حروف مسیر حساس نیست
+const PATHS_IGNORE_CASE: bool = cfg!(windows);
+
+fn same_path(a: &str, b: &str) -> bool {
+    let norm = |s: &str| s.replace('\\', "/");
+    if PATHS_IGNORE_CASE {
+        norm(a).eq_ignore_ascii_case(&norm(b))
+    } else {
+        norm(a) == norm(b)
+    }
+}
+
+fn regex(src: &str, case_insensitive: bool) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(src).case_insensitive(case_insensitive).build()
+}
+
+impl CompiledRule {
+    fn compile(index: usize, rule: &Rule) -> Result<Self, String> {
+        let err = |what: &str, e: &dyn std::fmt::Display| format!("rules[{}]: invalid {}: {}", index, what, e);
+
+        let process = Some(rule.process.trim()).filter(|p| !p.is_empty()).map(str::to_string);
+        let path_glob = rule.path_glob.as_deref()
+            .map(|g| Pattern::new(&g.replace('\\', "/")))
+            .transpose()
+            .map_err(|e| err("path_glob", &e))?;
+        let args = rule.args_regex.as_deref().map(|r| regex(r, false)).transpose().map_err(|e| err("args_regex", &e))?;
+        let name = rule.name_regex.as_deref().map(|r| regex(r, true)).transpose().map_err(|e| err("name_regex", &e))?;
+        let title = rule.title.as_deref().filter(|t| !t.is_empty()).map(str::to_lowercase);
+        let title_regex = rule.title_regex.as_deref()
+            .map(|r| regex(r, true))
+            .transpose()
+            .map_err(|e| err("title_regex", &e))?;
+
+        let rank = if title.is_some() || title_regex.is_some() {
+            Rank::Title
+        } else if rule.exe_path.is_some() {
+            Rank::ExePath
+        } else if path_glob.is_some() {
+            Rank::PathGlob
+        } else if args.is_some() {
+            Rank::Args
+        } else if process.is_some() {
+            Rank::Process
+        } else if name.is_some() {
+            Rank::NameRegex
+        } else {
+            return Err(format!("rules[{}]: needs at least one of process, exe_path, path_glob, args_regex, name_regex, title, title_regex", index));
+        };
+
+        Ok(Self { index, rank, process, exe_path: rule.exe_path.clone(), path_glob, args, name, title, title_regex })
+    }
+
+    fn matches(&self, facts: &ProcessFacts) -> bool {
+        let path = facts.exe_path.map(|p| p.to_string_lossy().replace('\\', "/"));
+
+        if let Some(p) = &self.process {
+            if !p.eq_ignore_ascii_case(facts.name) {
+                return false;
+            }
+        }
+        if let Some(expected) = &self.exe_path {
+            if !path.as_deref().is_some_and(|p| same_path(p, expected)) {
+                return false;
+            }
+        }
+        if let Some(glob) = &self.path_glob {
+            let opts = MatchOptions { case_sensitive: !PATHS_IGNORE_CASE, require_literal_separator: true, ..MatchOptions::new() };
+            if !path.as_deref().is_some_and(|p| glob.matches_with(p, opts)) {
+                return false;
+            }
+        }
+        if let Some(re) = &self.args {
+            if !re.is_match(&facts.args.join(" ")) {
+                return false;
+            }
+        }
+        if let Some(re) = &self.name {
+            if !re.is_match(facts.name) {
+                return false;
+            }
+        }
+        if let Some(needle) = &self.title {
+            if !facts.title.is_some_and(|t| t.to_lowercase().contains(needle.as_str())) {
+                return false;
+            }
+        }
+        if let Some(re) = &self.title_regex {
+            if !facts.title.is_some_and(|t| re.is_match(t)) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RuleSet {
+    rules: Vec<CompiledRule>,
+}
+
+impl RuleSet {
+    /// regex یا glob نامعتبر = خطا با شمارهٔ قانون
+    pub fn compile(rules: &[Rule]) -> Result<Self, String> {
+        let mut compiled = rules
+            .iter()
+            .enumerate()
+            .map(|(i, r)| CompiledRule::compile(i, r))
+            .collect::<Result<Vec<_>, _>>()?;
+        // sort پایدار است، پس در رتبهٔ برابر ترتیب فایل حفظ می‌شود
+        compiled.sort_by_key(|r| r.rank);
+        Ok(Self { rules: compiled })
+    }
+
+    /// اندیس قانون برنده در `Config::rules`
+    pub fn lookup(&self, facts: &ProcessFacts) -> Option<usize> {
+        let first = self.rules.iter().position(|r| r.matches(facts))?;
+        let hit = &self.rules[first];
+        if hit.rank == Rank::Process {
+            // مثل lang_for: در قوانین نام پروسه، تطابق دقیق حروف بر ترتیب فایل مقدم است
+            let exact = self.rules[first..]
+                .iter()
+                .take_while(|r| r.rank == Rank::Process)
+                .find(|r| r.process.as_deref() == Some(facts.name) && r.matches(facts));
+            if let Some(r) = exact {
+                return Some(r.index);
+            }
+        }
+        Some(hit.index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn rule(f: impl FnOnce(&mut Rule)) -> Rule {
+        let mut r = Rule { lang: "en".into(), ..Rule::default() };
+        f(&mut r);
+        r
+    }
+
+    fn lookup(set: &RuleSet, name: &str, exe: Option<&str>, args: &[&str]) -> Option<usize> {
+        let exe = exe.map(PathBuf::from);
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        set.lookup(&ProcessFacts { name, exe_path: exe.as_deref(), args: &args, title: None })
+    }
+
+    fn lookup_title(set: &RuleSet, name: &str, title: Option<&str>) -> Option<usize> {
+        set.lookup(&ProcessFacts { name, title, ..ProcessFacts::default() })
+    }
+
+    #[test]
+    fn two_pythons_are_told_apart_by_path_and_args() {
+        let rules = vec![
+            rule(|r| r.process = "python.exe".into()),
+            rule(|r| r.exe_path = Some("/opt/tools/python.exe".into())),
+            rule(|r| {
+                r.process = "python.exe".into();
+                r.args_regex = Some(r"manage\.py".into());
+            }),
+        ];
+        let set = RuleSet::compile(&rules).unwrap();
+        assert_eq!(lookup(&set, "python.exe", Some("/opt/tools/python.exe"), &["manage.py"]), Some(1));
+        assert_eq!(lookup(&set, "python.exe", Some("/usr/bin/python.exe"), &["manage.py", "runserver"]), Some(2));
+        assert_eq!(lookup(&set, "python.exe", Some("/usr/bin/python.exe"), &["script.py"]), Some(0));
+        assert_eq!(lookup(&set, "java.exe", None, &[]), None);
+    }
+
+    #[test]
+    fn precedence_is_path_glob_args_process_regex() {
+        let rules = vec![
+            rule(|r| r.name_regex = Some("^java".into())),
+            rule(|r| r.process = "java.exe".into()),
+            rule(|r| r.args_regex = Some("-jar idea".into())),
+            rule(|r| r.path_glob = Some("/apps/**/java.exe".into())),
+            rule(|r| r.exe_path = Some("/apps/jdk17/bin/java.exe".into())),
+        ];
+        let set = RuleSet::compile(&rules).unwrap();
+        let args = ["-jar idea"];
+        assert_eq!(lookup(&set, "java.exe", Some("/apps/jdk17/bin/java.exe"), &args), Some(4));
+        assert_eq!(lookup(&set, "java.exe", Some("/apps/jdk21/bin/java.exe"), &args), Some(3));
+        assert_eq!(lookup(&set, "java.exe", Some("/usr/bin/java.exe"), &args), Some(2));
+        assert_eq!(lookup(&set, "java.exe", Some("/usr/bin/java.exe"), &[]), Some(1));
+        assert_eq!(lookup(&set, "javaw.exe", None, &[]), Some(0));
+    }
+
+    #[test]
+    fn title_rules_win_and_fall_back_to_process() {
+        let rules = vec![
+            rule(|r| r.process = "chrome.exe".into()),
+            rule(|r| {
+                r.process = "chrome.exe".into();
+                r.title = Some("github".into());
+            }),
+            rule(|r| {
+                r.process = "chrome.exe".into();
+                r.title_regex = Some(r"\bJira\b|Atlassian".into());
+            }),
+            rule(|r| r.exe_path = Some("/opt/google/chrome/chrome.exe".into())),
+        ];
+        let set = RuleSet::compile(&rules).unwrap();
+        assert_eq!(lookup_title(&set, "chrome.exe", Some("Pull requests · GitHub - Google Chrome")), Some(1));
+        assert_eq!(lookup_title(&set, "chrome.exe", Some("[PROJ-12] jira board - Google Chrome")), Some(2));
+        assert_eq!(lookup_title(&set, "chrome.exe", Some("WhatsApp - Google Chrome")), Some(0));
+        assert_eq!(lookup_title(&set, "chrome.exe", None), Some(0));
+        // عنوان GitHub در برنامهٔ دیگر به قانون chrome نمی‌خورد
+        assert_eq!(lookup_title(&set, "firefox.exe", Some("GitHub")), None);
+        // قانون عنوان از قانون مسیر هم قوی‌تر است
+        let exe = PathBuf::from("/opt/google/chrome/chrome.exe");
+        let facts = ProcessFacts { name: "chrome.exe", exe_path: Some(&exe), args: &[], title: Some("GitHub") };
+        assert_eq!(set.lookup(&facts), Some(1));
+        assert_eq!(set.lookup(&ProcessFacts { title: Some("Inbox"), ..facts }), Some(3));
+    }
+
+    #[test]
+    fn exact_process_name_beats_case_insensitive() {
+        let rules = vec![
+            rule(|r| r.process = "CODE.EXE".into()),
+            rule(|r| r.process = "Code.exe".into()),
+        ];
+        let set = RuleSet::compile(&rules).unwrap();
+        assert_eq!(lookup(&set, "Code.exe", None, &[]), Some(1));
+        assert_eq!(lookup(&set, "code.exe", None, &[]), Some(0));
+    }
+
+    #[test]
+    fn equal_rank_keeps_file_order() {
+        let rules = vec![
+            rule(|r| r.name_regex = Some("^code".into())),
+            rule(|r| r.name_regex = Some("code".into())),
+        ];
+        let set = RuleSet::compile(&rules).unwrap();
+        assert_eq!(lookup(&set, "Code.exe", None, &[]), Some(0));
+    }
+
+    #[test]
+    fn glob_does_not_cross_separators_with_single_star() {
+        let set = RuleSet::compile(&[rule(|r| r.path_glob = Some("/apps/*/java.exe".into()))]).unwrap();
+        assert_eq!(lookup(&set, "java.exe", Some("/apps/jdk/java.exe"), &[]), Some(0));
+        assert_eq!(lookup(&set, "java.exe", Some("/apps/jdk/bin/java.exe"), &[]), None);
+        assert_eq!(lookup(&set, "java.exe", None, &[]), None);
+    }
+
+    #[test]
+    fn invalid_patterns_and_empty_rules_are_rejected() {
+        assert!(RuleSet::compile(&[rule(|r| r.name_regex = Some("(".into()))]).unwrap_err().contains("rules[0]"));
+        assert!(RuleSet::compile(&[rule(|r| r.path_glob = Some("[".into()))]).is_err());
+        assert!(RuleSet::compile(&[rule(|r| r.title_regex = Some("[".into()))]).unwrap_err().contains("title_regex"));
+        assert!(RuleSet::compile(&[rule(|_| {})]).is_err());
+    }
+}
@@ -0,0 +1,269 @@
+// src/switch.rs
+// تصمیم «عوض کنم یا نه» به صورت یک ماشین حالت خالص؛ بدون backend و بدون ساعت، تا با جدول تست شود.
+//
+// قاعده: فقط وقتی درخواست نمی‌دهیم که چیدمان *دیده‌شده* همان چیدمان مطلوب باشد.
+// چیدمانی که خودمان درخواست داده‌ایم کافی نیست؛ شاید کاربر بعدش دستی عوضش کرده باشد.
+
+/// آنچه دربارهٔ چیدمان فعلی می‌دانیم
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Known {
+    #[default]
+    Unknown,
+    /// درخواست دادیم ولی هنوز backend آن را گزارش نکرده
+    Requested(String),
+    /// backend همین را گزارش کرد
+    Observed(String),
+}
+
+/// برنامه‌ای که قانونی ندارد چه شود
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unmapped<'a> {
+    /// دست نزن؛ چیدمان برنامهٔ قبلی می‌ماند
+    Leave,
+    /// چیدمان پیش‌فرض سراسری
+    Default(&'a str),
+    /// چیدمانی که کاربر پیش از اولین تعویضِ قانونی داشت
+    Restore,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input<'a> {
+    /// پنجرهٔ فعال عوض شد و قانونی برایش چیدمان خواست
+    Focus(&'a str),
+    /// پنجرهٔ فعال عوض شد و قانونی نداشت
+    Unmapped(Unmapped<'a>),
+    /// چیدمانی که backend الان گزارش می‌دهد (None = نمی‌داند)
+    Observed(Option<&'a str>),
+    /// نتیجهٔ اجرای Switch قبلی
+    Requested { lang: &'a str, ok: bool },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Nothing,
+    Switch(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SwitchState {
+    pub layout: Known,
+    /// چیدمان مطلوب برای پنجرهٔ فعال
+    pub target: Option<String>,
+    /// پنجرهٔ فعال قانون داشت
+    pub rule_active: bool,
+    /// چیدمان کاربر پیش از آنکه قوانین دست به کار شوند؛ برای Unmapped::Restore
+    pub restore: Option<String>,
+}
+
+impl SwitchState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn next(&self, input: Input) -> (SwitchState, Action) {
+        let mut state = self.clone();
+        let action = match input {
+            Input::Focus(target) => {
+                if !self.rule_active {
+                    // از برنامهٔ بی‌قانون به برنامهٔ با قانون: چیدمان فعلی مال خود کاربر است
+                    if let Known::Observed(l) = &self.layout {
+                        state.restore = Some(l.clone());
+                    }
+                }
+                state.rule_active = true;
+                state.to(target)
+            }
+            Input::Unmapped(policy) => {
+                state.rule_active = false;
+                match policy {
+                    // آنچه می‌دانیم پاک نمی‌شود
+                    Unmapped::Leave => {
+                        state.target = None;
+                        Action::Nothing
+                    }
+                    Unmapped::Default(lang) => state.to(lang),
+                    Unmapped::Restore => match self.restore.clone() {
+                        Some(lang) => state.to(&lang),
+                        None => {
+                            state.target = None;
+                            Action::Nothing
+                        }
+                    },
+                }
+            }
+            Input::Observed(layout) => {
+                state.layout = layout.map_or(Known::Unknown, |l| Known::Observed(l.to_string()));
+                Action::Nothing
+            }
+            Input::Requested { lang, ok } => {
+                state.layout = if ok { Known::Requested(lang.to_string()) } else { Known::Unknown };
+                Action::Nothing
+            }
+        };
+        (state, action)
+    }
+
+    fn to(&mut self, target: &str) -> Action {
+        self.target = Some(target.to_string());
+        if self.layout == Known::Observed(target.to_string()) {
+            Action::Nothing
+        } else {
+            Action::Switch(target.to_string())
+        }
+    }
+
+    /// مثل `next` ولی خود حالت را جلو می‌برد
+    pub fn apply(&mut self, input: Input) -> Action {
+        let (state, action) = self.next(input);
+        *self = state;
+        action
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Input::*;
+    use super::Unmapped::{Default as DefaultLang, Leave, Restore};
+
+    fn switch(t: &str) -> Action {
+        Action::Switch(t.to_string())
+    }
+
+    /// هر ردیف: (ورودی، خروجی مورد انتظار)
+    fn run(steps: &[(Input, Action)]) {
+        let mut s = SwitchState::new();
+        for (i, (input, expected)) in steps.iter().enumerate() {
+            let action = s.apply(*input);
+            assert_eq!(&action, expected, "step {} ({:?}), state {:?}", i, input, s);
+            if let Action::Switch(lang) = &action {
+                s.apply(Requested { lang, ok: true });
+            }
+        }
+    }
+
+    #[test]
+    fn sequences() {
+        let cases: &[(&str, &[(Input, Action)])] = &[
+            (
+                "already on the target layout",
+                &[(Observed(Some("fa")), Action::Nothing), (Focus("fa"), Action::Nothing)],
+            ),
+            (
+                "different layout is switched",
+                &[(Observed(Some("en")), Action::Nothing), (Focus("fa"), switch("fa"))],
+            ),
+            (
+                "same language, different app, after a manual switch",
+                &[
+                    (Focus("fa"), switch("fa")),
+                    (Observed(Some("fa")), Action::Nothing),
+                    (Observed(Some("en")), Action::Nothing),
+                    (Focus("fa"), switch("fa")),
+                ],
+            ),
+            (
+                "same language, different app, nothing changed in between",
+                &[
+                    (Focus("fa"), switch("fa")),
+                    (Observed(Some("fa")), Action::Nothing),
+                    (Focus("fa"), Action::Nothing),
+                ],
+            ),
+            (
+                "app without a rule in between keeps what we know",
+                &[
+                    (Focus("fa"), switch("fa")),
+                    (Observed(Some("fa")), Action::Nothing),
+                    (Unmapped(Leave), Action::Nothing),
+                    (Observed(Some("fa")), Action::Nothing),
+                    (Focus("fa"), Action::Nothing),
+                ],
+            ),
+            (
+                "user switched inside the app without a rule",
+                &[
+                    (Focus("fa"), switch("fa")),
+                    (Observed(Some("fa")), Action::Nothing),
+                    (Unmapped(Leave), Action::Nothing),
+                    (Observed(Some("en")), Action::Nothing),
+                    (Focus("fa"), switch("fa")),
+                ],
+            ),
+            (
+                "a request alone is not proof; repeat until observed",
+                &[(Focus("fa"), switch("fa")), (Focus("fa"), switch("fa"))],
+            ),
+            (
+                "backend cannot report the layout",
+                &[
+                    (Observed(None), Action::Nothing),
+                    (Focus("en"), switch("en")),
+                    (Observed(None), Action::Nothing),
+                    (Focus("en"), switch("en")),
+                ],
+            ),
+            (
+                "default policy switches unmapped apps",
+                &[
+                    (Focus("fa"), switch("fa")),
+                    (Observed(Some("fa")), Action::Nothing),
+                    (Unmapped(DefaultLang("en")), switch("en")),
+                    (Observed(Some("en")), Action::Nothing),
+                    (Unmapped(DefaultLang("en")), Action::Nothing),
+                ],
+            ),
+            (
+                "restore brings back the user's layout",
+                &[
+                    (Observed(Some("ru")), Action::Nothing),
+                    (Focus("fa"), switch("fa")),
+                    (Observed(Some("fa")), Action::Nothing),
+                    (Focus("en"), switch("en")),
+                    (Observed(Some("en")), Action::Nothing),
+                    (Unmapped(Restore), switch("ru")),
+                    (Observed(Some("ru")), Action::Nothing),
+                    (Unmapped(Restore), Action::Nothing),
+                ],
+            ),
+            (
+                "restore with nothing to restore leaves it",
+                &[(Focus("fa"), switch("fa")), (Unmapped(Restore), Action::Nothing)],
+            ),
+            (
+                "manual switch in an unmapped app becomes the new restore point",
+                &[
+                    (Observed(Some("en")), Action::Nothing),
+                    (Focus("fa"), switch("fa")),
+                    (Unmapped(Restore), switch("en")),
+                    (Observed(Some("de")), Action::Nothing),
+                    (Focus("fa"), switch("fa")),
+                    (Unmapped(Restore), switch("de")),
+                ],
+            ),
+        ];
+        for (name, steps) in cases {
+            eprintln!("case: {}", name);
+            run(steps);
+        }
+    }
+
+    #[test]
+    fn failed_request_forgets_the_layout() {
+        let mut s = SwitchState::new();
+        s.apply(Observed(Some("en")));
+        assert_eq!(s.apply(Focus("fa")), switch("fa"));
+        s.apply(Requested { lang: "fa", ok: false });
+        assert_eq!(s.layout, Known::Unknown);
+        assert_eq!(s.target.as_deref(), Some("fa"));
+    }
+
+    #[test]
+    fn next_is_pure() {
+        let s = SwitchState::new();
+        let (after, action) = s.next(Focus("fa"));
+        assert_eq!(s, SwitchState::new());
+        assert_eq!(action, switch("fa"));
+        assert_eq!(after.target.as_deref(), Some("fa"));
+    }
+}
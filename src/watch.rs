@@ -0,0 +1,849 @@
+// src/watch.rs
+// منطق watcher جدا از پلتفرم؛ هر LayoutBackend (حتی mock) را می‌پذیرد
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::{ForegroundApp, LayoutBackend};
+use crate::config::{get_config_path, Config, ConfigWatcher, Rule, UnmappedPolicy};
+use crate::ipc::{Command, Response, Status};
+use crate::learn::Learner;
+use crate::layouts::{Layout, LayoutRegistry};
+use crate::rules::{ProcessFacts, RuleSet};
+use crate::switch::{Action, Input, Known, SwitchState, Unmapped};
+
+/// هر چند وقت پنجره‌های بسته‌شده از حافظهٔ per-window پاک می‌شوند
+const WINDOW_GC_INTERVAL: Duration = Duration::from_secs(30);
+/// سقف حافظه برای backendهایی که بسته شدن پنجره را تشخیص نمی‌دهند
+const MAX_WINDOWS: usize = 256;
+/// بعد از هر تعویض چند بار (با این فاصله) چیدمان خوانده می‌شود؛ PostMessage در ویندوز ناهمگام است
+const VERIFY_CHECKS: u32 = 3;
+const VERIFY_DELAY: Duration = Duration::from_millis(30);
+/// اگر باز هم چیدمان دیگری گزارش شد چند بار دیگر درخواست داده شود
+const SWITCH_RETRIES: u32 = 1;
+/// چند رویداد آخر در `Status` به GUI و CLI می‌رسد
+const RECENT_EVENTS: usize = 10;
+
+/// رویدادهای چیدمان برای بقیهٔ بخش‌ها؛ داخل پروسه با `Control::subscribe`،
+/// و برای GUI/CLI چند مورد آخر در `Status::events`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum LayoutEvent {
+    /// کاربر (نه watcher) چیدمان را عوض کرد
+    ManualOverride { app: Option<String>, from: String, to: String },
+    /// backend تعویض را پذیرفت ولی حتی بعد از تکرار چیدمان دیگری گزارش می‌دهد
+    SwitchUnconfirmed { app: String, wanted: String, actual: Option<String> },
+}
+
+impl fmt::Display for LayoutEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ManualOverride { app, from, to } => {
+                write!(f, "manual switch {} -> {} in {}", from, to, app.as_deref().unwrap_or("?"))
+            }
+            Self::SwitchUnconfirmed { app, wanted, actual } => {
+                write!(f, "{}: wanted {}, layout is still {}", app, wanted, actual.as_deref().unwrap_or("?"))
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Remembered {
+    lang: String,
+    seen: Instant,
+}
+
+#[derive(Debug, Default)]
+pub struct Watcher {
+    last_pid: Option<u32>,
+    last_window: Option<u64>,
+    last_title: Option<String>,
+    /// تصمیم عوض کردن یا نکردن
+    switch: SwitchState,
+    /// قوانینی که `compiled` از رویشان ساخته شده؛ فقط با تغییر کانفیگ دوباره کامپایل می‌شود
+    rules: Vec<Rule>,
+    compiled: RuleSet,
+    /// نام برنامهٔ فعال در آخرین step، برای حالت یادگیری
+    app: Option<String>,
+    /// برنامهٔ فعال قانون صریح دارد؛ آن وقت یادگرفتن بی‌فایده است چون قانون همیشه مقدم است
+    ruled: bool,
+    learner: Learner,
+    /// حالت per_window: آخرین چیدمان هر پنجره
+    windows: HashMap<u64, Remembered>,
+    last_gc: Option<Instant>,
+    /// خبر تغییر چیدمان از backend؛ None یعنی هر دور خودمان می‌پرسیم
+    layout_rx: Option<Receiver<()>>,
+    /// رویدادهایی که هنوز `take_events` برنداشته
+    events: Vec<LayoutEvent>,
+}
+
+impl Watcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// پنجرهٔ فعال در step بعدی دوباره بررسی می‌شود، حتی اگر عوض نشده باشد
+    pub fn reset(&mut self) {
+        self.last_pid = None;
+        self.last_window = None;
+        self.last_title = None;
+    }
+
+    pub fn app(&self) -> Option<&str> {
+        self.app.as_deref()
+    }
+
+    /// چیدمانی که دیده یا درخواست شده
+    pub fn layout(&self) -> Option<&str> {
+        match &self.switch.layout {
+            Known::Unknown => None,
+            Known::Requested(l) | Known::Observed(l) => Some(l),
+        }
+    }
+
+    pub fn target(&self) -> Option<&str> {
+        self.switch.target.as_deref()
+    }
+
+    /// اشتراک تغییر چیدمان backend، اگر داشته باشد؛ یک بار پیش از اولین step
+    pub fn subscribe<B: LayoutBackend + ?Sized>(&mut self, backend: &mut B) {
+        self.layout_rx = backend.layout_changes();
+    }
+
+    pub fn take_events(&mut self) -> Vec<LayoutEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// یک دور بررسی پنجرهٔ فعال؛ اگر چیدمانی درخواست شد تگ آن برگردانده می‌شود
+    pub fn step<B: LayoutBackend + ?Sized>(&mut self, backend: &mut B, cfg: &Config) -> Option<String> {
+        let Some(app) = backend.foreground_app() else {
+            self.app = None;
+            return None;
+        };
+        self.app = Some(app.name.clone());
+        if Some(app.pid) == self.last_pid && app.window == self.last_window && app.title == self.last_title {
+            return None;
+        }
+        let window_changed = app.window != self.last_window;
+        self.last_pid = Some(app.pid);
+        self.last_window = app.window;
+        self.last_title = app.title.clone();
+
+        // اول چیدمان خود این پنجره؛ در ویندوز هر thread چیدمان جدا دارد و دانستهٔ پنجرهٔ قبلی کهنه است
+        let registry = cfg.registry();
+        let observed = backend.current_layout(&registry);
+        self.switch.apply(Input::Observed(observed.as_deref()));
+
+        // برگشت به پنجره‌ای که قبلاً دیده شده: همان چیدمانی که کاربر آنجا داشت، مقدم بر قوانین
+        let remembered = app.window
+            .filter(|_| cfg.settings.per_window && window_changed)
+            .and_then(|w| self.windows.get(&w))
+            .map(|m| m.lang.clone());
+        let rule = self.explicit_rule(cfg, &app);
+        self.ruled = rule.is_some();
+        let target = remembered.or(rule).or_else(|| {
+            cfg.settings.learning.then(|| cfg.learned_lang(&app.name).map(str::to_string)).flatten()
+        });
+        let input = match &target {
+            Some(lang) => Input::Focus(lang),
+            None => Input::Unmapped(match cfg.defaults.unmapped {
+                UnmappedPolicy::Leave => Unmapped::Leave,
+                UnmappedPolicy::Default => Unmapped::Default(&cfg.defaults.lang),
+                UnmappedPolicy::Restore => Unmapped::Restore,
+            }),
+        };
+        let Action::Switch(lang) = self.switch.apply(input) else {
+            return None;
+        };
+        self.request(backend, cfg, &registry, &app.name, lang)
+    }
+
+    /// فقط قوانین صریح؛ مورد یادگرفته بعد از آن‌ها در `step` بررسی می‌شود
+    fn explicit_rule(&mut self, cfg: &Config, app: &ForegroundApp) -> Option<String> {
+        if self.rules != cfg.rules {
+            // کانفیگ قبلاً validate شده؛ اگر باز هم خطا داشت هیچ قانونی اعمال نمی‌شود
+            self.compiled = RuleSet::compile(&cfg.rules).unwrap_or_else(|e| {
+                eprintln!("Invalid rules: {}", e);
+                RuleSet::default()
+            });
+            self.rules = cfg.rules.clone();
+        }
+        let facts = ProcessFacts {
+            name: &app.name,
+            exe_path: app.exe_path.as_deref(),
+            args: &app.args,
+            title: app.title.as_deref(),
+        };
+        self.compiled.lookup(&facts).map(|i| cfg.rules[i].lang.clone())
+    }
+
+    fn request<B: LayoutBackend + ?Sized>(
+        &mut self,
+        backend: &mut B,
+        cfg: &Config,
+        registry: &LayoutRegistry,
+        app: &str,
+        lang: String,
+    ) -> Option<String> {
+        let Some(layout) = registry.get(&lang) else {
+            eprintln!("No layout known for '{}' ({})", lang, app);
+            self.switch.apply(Input::Requested { lang: &lang, ok: false });
+            return None;
+        };
+        let ok = backend.set_layout(layout);
+        self.switch.apply(Input::Requested { lang: &lang, ok });
+        if !ok {
+            return None;
+        }
+        if let Some(actual) = self.confirm(backend, registry, layout) {
+            eprintln!(
+                "Layout {} for {} was not applied (backend reports {})",
+                lang,
+                app,
+                actual.as_deref().unwrap_or("unknown")
+            );
+            self.switch.apply(Input::Observed(actual.as_deref()));
+            self.events.push(LayoutEvent::SwitchUnconfirmed { app: app.to_string(), wanted: lang, actual });
+            return None;
+        }
+        println!("Requested layout {} for {}", lang, app);
+        if cfg.settings.learning {
+            self.learner.expect(app, &lang);
+        }
+        Some(lang)
+    }
+
+    /// بعد از set_layout چیدمان را می‌خواند و در صورت لزوم دوباره درخواست می‌دهد.
+    /// None یعنی تأیید شد (یا backend چیدمان را نمی‌داند)؛ Some(چیدمان گزارش‌شده) یعنی اعمال نشد
+    fn confirm<B: LayoutBackend + ?Sized>(
+        &mut self,
+        backend: &mut B,
+        registry: &LayoutRegistry,
+        layout: &Layout,
+    ) -> Option<Option<String>> {
+        let mut actual = None;
+        for attempt in 0..=SWITCH_RETRIES {
+            if attempt > 0 && !backend.set_layout(layout) {
+                break;
+            }
+            for check in 0..VERIFY_CHECKS {
+                if check > 0 {
+                    thread::sleep(VERIFY_DELAY);
+                }
+                match backend.current_layout(registry) {
+                    None => return None,
+                    Some(l) if l == layout.tag => {
+                        self.switch.apply(Input::Observed(Some(&l)));
+                        return None;
+                    }
+                    other => actual = other,
+                }
+            }
+        }
+        Some(actual)
+    }
+
+    /// هر دور (حتی بدون تغییر فوکوس) چیدمان فعلی را می‌خواند: برای حافظهٔ per-window و حالت یادگیری.
+    /// Some((برنامه، چیدمان)) یعنی باید در `learned` ثبت شود
+    pub fn observe<B: LayoutBackend + ?Sized>(&mut self, backend: &mut B, cfg: &Config, now: Instant) -> Option<(String, String)> {
+        if cfg.settings.per_window {
+            self.collect_windows(backend, now);
+        } else {
+            self.windows.clear();
+        }
+        let notified = self.layout_changed();
+        if !notified && !cfg.settings.learning && !cfg.settings.per_window {
+            return None;
+        }
+        let layout = backend.current_layout(&cfg.registry());
+        // فقط تفاوت با چیدمانی که خودمان دیده یا تأیید کرده‌ایم دستی حساب می‌شود
+        if let (Known::Observed(from), Some(to)) = (&self.switch.layout, &layout) {
+            if from != to {
+                println!("Layout changed manually from {} to {}", from, to);
+                self.events.push(LayoutEvent::ManualOverride { app: self.app.clone(), from: from.clone(), to: to.clone() });
+            }
+        }
+        self.switch.apply(Input::Observed(layout.as_deref()));
+
+        if let (true, Some(w), Some(lang)) = (cfg.settings.per_window, self.last_window, &layout) {
+            self.windows.insert(w, Remembered { lang: lang.clone(), seen: now });
+            if self.windows.len() > MAX_WINDOWS {
+                let oldest = self.windows.iter().min_by_key(|(_, m)| m.seen).map(|(w, _)| *w);
+                if let Some(w) = oldest {
+                    self.windows.remove(&w);
+                }
+            }
+        }
+
+        if !cfg.settings.learning || self.ruled {
+            return None;
+        }
+        let app = self.app.as_ref()?;
+        let lang = self.learner.observe(app, layout.as_deref(), now, &cfg.settings)?;
+        Some((app.clone(), lang))
+    }
+
+    /// خبری از backend رسیده؟ بدون اشتراک همیشه true (polling)
+    fn layout_changed(&mut self) -> bool {
+        let Some(rx) = &self.layout_rx else { return true };
+        let mut changed = false;
+        loop {
+            match rx.try_recv() {
+                Ok(()) => changed = true,
+                Err(TryRecvError::Empty) => return changed,
+                Err(TryRecvError::Disconnected) => {
+                    eprintln!("Layout change events stopped, polling instead");
+                    self.layout_rx = None;
+                    return true;
+                }
+            }
+        }
+    }
+
+    /// پنجره‌هایی که بسته شده‌اند از حافظه پاک می‌شوند
+    fn collect_windows<B: LayoutBackend + ?Sized>(&mut self, backend: &mut B, now: Instant) {
+        if self.last_gc.is_some_and(|t| now.duration_since(t) < WINDOW_GC_INTERVAL) {
+            return;
+        }
+        self.last_gc = Some(now);
+        self.windows.retain(|w, _| backend.window_alive(*w));
+    }
+}
+
+/// کنترل watch_loop از بیرون: منوی تری و فرمان‌های IPC
+#[derive(Debug)]
+pub struct Control {
+    /// false یعنی حلقه تمام شود
+    pub running: AtomicBool,
+    pub paused: AtomicBool,
+    reload: AtomicBool,
+    apply_now: AtomicBool,
+    status: Mutex<Status>,
+    subscribers: Mutex<Vec<Sender<LayoutEvent>>>,
+    recent: Mutex<VecDeque<LayoutEvent>>,
+}
+
+impl Default for Control {
+    fn default() -> Self {
+        Self {
+            running: AtomicBool::new(true),
+            paused: AtomicBool::new(false),
+            reload: AtomicBool::new(false),
+            apply_now: AtomicBool::new(false),
+            status: Mutex::new(Status::default()),
+            subscribers: Mutex::new(Vec::new()),
+            recent: Mutex::new(VecDeque::new()),
+        }
+    }
+}
+
+impl Control {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        let was = self.paused.swap(paused, Ordering::SeqCst);
+        if was && !paused {
+            // بعد از ادامه، پنجرهٔ فعال را بدون انتظار برای فوکوس بعدی اعمال کن
+            self.apply_now.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// رویدادهای چیدمان از این به بعد
+    pub fn subscribe(&self) -> Receiver<LayoutEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// مشترک‌هایی که receiver را رها کرده‌اند حذف می‌شوند
+    fn dispatch(&self, events: Vec<LayoutEvent>) {
+        if events.is_empty() {
+            return;
+        }
+        let mut recent = self.recent.lock().unwrap();
+        recent.extend(events.iter().cloned());
+        while recent.len() > RECENT_EVENTS {
+            recent.pop_front();
+        }
+        drop(recent);
+        self.subscribers.lock().unwrap().retain(|tx| events.iter().all(|e| tx.send(e.clone()).is_ok()));
+    }
+
+    pub fn status(&self) -> Status {
+        let mut s = self.status.lock().unwrap().clone();
+        s.pid = std::process::id();
+        s.paused = self.paused.load(Ordering::SeqCst);
+        s.events = self.recent.lock().unwrap().iter().cloned().collect();
+        s
+    }
+
+    /// پاسخ فرمان‌های IPC؛ کار واقعی در دور بعدی watch_loop انجام می‌شود
+    pub fn handle(&self, cmd: Command) -> Response {
+        match cmd {
+            Command::Ping => Response::ok(),
+            Command::Status => Response::with_status(self.status()),
+            Command::Pause => {
+                self.set_paused(true);
+                Response::ok()
+            }
+            Command::Resume => {
+                self.set_paused(false);
+                Response::ok()
+            }
+            Command::Reload => {
+                self.reload.store(true, Ordering::SeqCst);
+                Response::ok()
+            }
+            Command::ApplyNow => {
+                self.apply_now.store(true, Ordering::SeqCst);
+                Response::ok()
+            }
+            Command::Shutdown => {
+                self.running.store(false, Ordering::SeqCst);
+                Response::ok()
+            }
+            // آرگومان‌های نسخهٔ دوم را خود باینری تفسیر می‌کند
+            Command::Activate { .. } => Response::ok(),
+        }
+    }
+
+    fn publish(&self, watcher: &Watcher, config: &ConfigWatcher) {
+        let cfg = config.config();
+        *self.status.lock().unwrap() = Status {
+            app: watcher.app().map(str::to_string),
+            layout: watcher.layout().map(str::to_string),
+            target: watcher.target().map(str::to_string),
+            config_path: config.path().to_path_buf(),
+            generation: cfg.generation,
+            rules: cfg.rules.len(),
+            ..Status::default()
+        };
+    }
+}
+
+/// poll_ms هم فاصلهٔ polling است و هم حداکثر زمان انتظار برای رویداد (تا توقف watcher معطل نماند).
+/// فایل کانفیگ فقط وقتی عوض شده باشد دوباره خوانده می‌شود.
+pub fn watch_loop<B: LayoutBackend + ?Sized>(backend: &mut B, control: &Control) {
+    let mut watcher = Watcher::new();
+    watcher.subscribe(backend);
+    let mut config = ConfigWatcher::new(get_config_path());
+    let mut focus_changed = true;
+    while control.running.load(Ordering::SeqCst) {
+        if control.reload.swap(false, Ordering::SeqCst) {
+            config.reload();
+        } else {
+            config.poll();
+        }
+        let cfg = config.config();
+        let wait = Duration::from_millis(cfg.settings.poll_ms);
+        if control.paused.load(Ordering::SeqCst) {
+            control.publish(&watcher, &config);
+            backend.wait_for_focus_change(wait);
+            continue;
+        }
+        if control.apply_now.swap(false, Ordering::SeqCst) {
+            watcher.reset();
+            focus_changed = true;
+        }
+        if focus_changed {
+            watcher.step(backend, cfg);
+        }
+        if let Some((app, lang)) = watcher.observe(backend, cfg, Instant::now()) {
+            match Config::update_at(config.path(), |c| {
+                c.learn(&app, &lang);
+            }) {
+                Ok(_) => println!("Learned layout {} for {}", lang, app),
+                Err(e) => eprintln!("Could not save learned layout for {}: {}", app, e),
+            }
+        }
+        control.dispatch(watcher.take_events());
+        control.publish(&watcher, &config);
+        focus_changed = backend.wait_for_focus_change(wait);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockBackend;
+
+    fn cfg(pairs: &[(&str, &str)]) -> Config {
+        let mut c = Config::default();
+        for (k, v) in pairs {
+            c.set_lang(k, v);
+        }
+        c
+    }
+
+    #[test]
+    fn switches_when_focus_moves_to_mapped_app() {
+        let cfg = cfg(&[("chrome.exe", "fa"), ("Code.exe", "en")]);
+        let mut be = MockBackend::new(&["en", "fa"]);
+        let mut w = Watcher::new();
+
+        be.focus(1, "chrome.exe");
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("fa"));
+        // همان pid دوباره درخواست نمی‌شود
+        assert_eq!(w.step(&mut be, &cfg), None);
+
+        be.focus(2, "code.exe");
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("en"));
+        assert_eq!(be.requests, vec!["fa", "en"]);
+    }
+
+    #[test]
+    fn manual_switch_between_same_language_apps_is_undone() {
+        let cfg = cfg(&[("chrome.exe", "fa"), ("Telegram.exe", "fa")]);
+        let mut be = MockBackend::new(&["en", "fa"]);
+        let mut w = Watcher::new();
+
+        be.focus(1, "chrome.exe");
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("fa"));
+        // کاربر دستی en می‌کند؛ برنامهٔ بعدی هم fa می‌خواهد
+        be.layout = Some("en".into());
+        be.focus(2, "Telegram.exe");
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("fa"));
+        // از برنامهٔ بی‌قانون رد می‌شویم و چیدمان fa می‌ماند: درخواست تکراری نمی‌رود
+        be.focus(3, "notepad.exe");
+        assert_eq!(w.step(&mut be, &cfg), None);
+        be.focus(1, "chrome.exe");
+        assert_eq!(w.step(&mut be, &cfg), None);
+        assert_eq!(be.requests, vec!["fa", "fa"]);
+    }
+
+    #[test]
+    fn unmapped_policy_default_and_restore() {
+        let mut cfg = cfg(&[("chrome.exe", "fa")]);
+        cfg.defaults.lang = "en".into();
+        let mut be = MockBackend::new(&["ru", "en", "fa"]);
+        let mut w = Watcher::new();
+
+        // leave: چیدمان chrome به notepad نشت می‌کند
+        be.focus(1, "chrome.exe");
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("fa"));
+        be.focus(2, "notepad.exe");
+        assert_eq!(w.step(&mut be, &cfg), None);
+        assert_eq!(be.layout.as_deref(), Some("fa"));
+
+        cfg.defaults.unmapped = UnmappedPolicy::Default;
+        be.focus(3, "notepad.exe");
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("en"));
+
+        // restore: چیدمانی که کاربر پیش از chrome داشت
+        cfg.defaults.unmapped = UnmappedPolicy::Restore;
+        be.layout = Some("ru".into());
+        be.focus(4, "notepad.exe");
+        assert_eq!(w.step(&mut be, &cfg), None);
+        be.focus(1, "chrome.exe");
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("fa"));
+        be.focus(2, "notepad.exe");
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("ru"));
+    }
+
+    #[test]
+    fn unmapped_and_unknown_layouts_do_nothing() {
+        let cfg = cfg(&[("chrome.exe", "xx")]);
+        let mut be = MockBackend::new(&["en", "fa"]);
+        let mut w = Watcher::new();
+
+        be.focus(1, "notepad.exe");
+        assert_eq!(w.step(&mut be, &cfg), None);
+        be.focus(2, "chrome.exe");
+        assert_eq!(w.step(&mut be, &cfg), None);
+        assert!(be.requests.is_empty());
+    }
+
+    #[test]
+    fn path_and_args_rules_pick_between_same_named_apps() {
+        use crate::backend::ForegroundApp;
+
+        let mut cfg = cfg(&[("python.exe", "en")]);
+        cfg.rules.push(Rule { args_regex: Some("persian_notes".into()), lang: "fa".into(), ..Rule::default() });
+        let mut be = MockBackend::new(&["en", "fa"]);
+        let mut w = Watcher::new();
+
+        let app = |pid, args: &[&str]| ForegroundApp {
+            pid,
+            window: None,
+            name: "python.exe".into(),
+            exe_path: None,
+            args: args.iter().map(|s| s.to_string()).collect(),
+            title: None,
+        };
+        be.focus_app(app(1, &["persian_notes.py"]));
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("fa"));
+        be.focus_app(app(2, &["build.py"]));
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("en"));
+    }
+
+    #[test]
+    fn title_change_in_same_window_reapplies_rules() {
+        let mut cfg = cfg(&[("chrome.exe", "fa")]);
+        cfg.rules.push(Rule { process: "chrome.exe".into(), title: Some("GitHub".into()), lang: "en".into(), ..Rule::default() });
+        let mut be = MockBackend::new(&["en", "fa"]);
+        let mut w = Watcher::new();
+
+        be.focus(1, "chrome.exe");
+        be.set_title("Chats - Google Chrome");
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("fa"));
+        be.set_title("sepy-dev/LangSwitcher · GitHub - Google Chrome");
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("en"));
+        // عنوان عوض شد ولی زبان همان است؛ درخواست تکراری نمی‌رود
+        be.set_title("Issues · GitHub - Google Chrome");
+        assert_eq!(w.step(&mut be, &cfg), None);
+        be.set_title("Chats - Google Chrome");
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("fa"));
+        assert_eq!(be.requests, vec!["fa", "en", "fa"]);
+    }
+
+    #[test]
+    fn learning_mode_records_and_uses_manual_layouts() {
+        let mut cfg = cfg(&[("Code.exe", "en")]);
+        cfg.settings.learning = true;
+        cfg.settings.learn_debounce_ms = 0;
+        cfg.settings.learn_confidence = 2;
+        let mut be = MockBackend::new(&["en", "fa"]);
+        let mut w = Watcher::new();
+        let t = Instant::now();
+
+        be.focus(1, "Telegram.exe");
+        assert_eq!(w.step(&mut be, &cfg), None);
+        assert_eq!(w.observe(&mut be, &cfg, t), None);
+        let mut learned = None;
+        for _ in 0..2 {
+            // کاربر دستی به fa می‌رود و بعد چند لحظه می‌ماند
+            be.layout = Some("en".into());
+            w.observe(&mut be, &cfg, t);
+            be.layout = Some("fa".into());
+            w.observe(&mut be, &cfg, t);
+            learned = learned.or(w.observe(&mut be, &cfg, t));
+        }
+        assert_eq!(learned, Some(("Telegram.exe".to_string(), "fa".to_string())));
+        cfg.learn("Telegram.exe", "fa");
+
+        // قانون صریح بر یادگرفته مقدم است و تعویض خود watcher یاد گرفته نمی‌شود
+        be.focus(2, "Code.exe");
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("en"));
+        be.focus(3, "Telegram.exe");
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("fa"));
+        assert_eq!(w.observe(&mut be, &cfg, t), None);
+
+        // برای برنامه‌ای که قانون دارد رأی جمع نمی‌شود؛ وگرنه Promote قانون را بی‌صدا عوض می‌کرد
+        be.focus(6, "Code.exe");
+        w.step(&mut be, &cfg);
+        for _ in 0..3 {
+            be.layout = Some("en".into());
+            assert_eq!(w.observe(&mut be, &cfg, t), None);
+            be.layout = Some("fa".into());
+            assert_eq!(w.observe(&mut be, &cfg, t), None);
+            assert_eq!(w.observe(&mut be, &cfg, t), None);
+        }
+
+        cfg.settings.learning = false;
+        be.focus(4, "Code.exe");
+        w.step(&mut be, &cfg);
+        be.focus(5, "Telegram.exe");
+        assert_eq!(w.step(&mut be, &cfg), None);
+    }
+
+    #[test]
+    fn per_window_mode_restores_each_windows_layout() {
+        let mut cfg = cfg(&[("Code.exe", "en")]);
+        cfg.settings.per_window = true;
+        let mut be = MockBackend::new(&["fa", "en"]);
+        let mut w = Watcher::new();
+        let t = Instant::now();
+
+        // پنجرهٔ اول با قانون en باز می‌شود و کاربر دستی fa می‌کند
+        be.focus_window(1, "Code.exe", 100);
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("en"));
+        be.layout = Some("fa".into());
+        w.observe(&mut be, &cfg, t);
+
+        // پنجرهٔ دوم همان پروسه، قانون اعمال می‌شود
+        be.focus_window(1, "Code.exe", 200);
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("en"));
+        w.observe(&mut be, &cfg, t);
+
+        be.focus_window(1, "Code.exe", 100);
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("fa"));
+        w.observe(&mut be, &cfg, t);
+        be.focus_window(1, "Code.exe", 200);
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("en"));
+        assert_eq!(be.requests, vec!["en", "en", "fa", "en"]);
+    }
+
+    #[test]
+    fn closed_windows_are_forgotten() {
+        let mut cfg = Config::default();
+        cfg.settings.per_window = true;
+        let mut be = MockBackend::new(&["en", "fa"]);
+        let mut w = Watcher::new();
+        let t = Instant::now();
+
+        for win in [1, 2] {
+            be.focus_window(7, "notepad.exe", win);
+            w.step(&mut be, &cfg);
+            w.observe(&mut be, &cfg, t);
+        }
+        assert_eq!(w.windows.len(), 2);
+        be.closed.insert(1);
+        // تا بازهٔ GC نرسیده چیزی پاک نمی‌شود
+        w.observe(&mut be, &cfg, t + Duration::from_secs(1));
+        assert_eq!(w.windows.len(), 2);
+        w.observe(&mut be, &cfg, t + WINDOW_GC_INTERVAL);
+        assert!(!w.windows.contains_key(&1));
+        assert!(w.windows.contains_key(&2));
+
+        cfg.settings.per_window = false;
+        w.observe(&mut be, &cfg, t + WINDOW_GC_INTERVAL);
+        assert!(w.windows.is_empty());
+    }
+
+    #[test]
+    fn no_foreground_window_is_a_noop() {
+        let mut be = MockBackend::new(&["en"]);
+        assert_eq!(Watcher::new().step(&mut be, &Config::default()), None);
+    }
+
+    #[test]
+    fn control_commands() {
+        let c = Control::new();
+        assert!(c.handle(Command::Ping).ok);
+        c.handle(Command::Pause);
+        assert!(c.handle(Command::Status).status.unwrap().paused);
+        assert!(!c.apply_now.load(Ordering::SeqCst));
+        c.handle(Command::Resume);
+        assert!(c.apply_now.load(Ordering::SeqCst));
+        assert_eq!(c.status().pid, std::process::id());
+
+        c.handle(Command::Reload);
+        assert!(c.reload.load(Ordering::SeqCst));
+        c.handle(Command::Shutdown);
+        assert!(!c.running.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn status_reports_what_the_watcher_decided() {
+        let cfg = cfg(&[("chrome.exe", "fa")]);
+        let mut be = MockBackend::new(&["en", "fa"]);
+        let mut w = Watcher::new();
+        be.focus(1, "chrome.exe");
+        w.step(&mut be, &cfg);
+        assert_eq!((w.app(), w.layout(), w.target()), (Some("chrome.exe"), Some("fa"), Some("fa")));
+
+        // reset یعنی همان پنجره دوباره بررسی شود
+        be.layout = Some("en".into());
+        assert_eq!(w.step(&mut be, &cfg), None);
+        w.reset();
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("fa"));
+    }
+
+    #[test]
+    fn switch_is_retried_when_the_backend_ignores_it() {
+        let cfg = cfg(&[("chrome.exe", "fa")]);
+        let mut be = MockBackend::new(&["en", "fa"]);
+        let mut w = Watcher::new();
+        be.ignore_switches = 1;
+        be.focus(1, "chrome.exe");
+        assert_eq!(w.step(&mut be, &cfg).as_deref(), Some("fa"));
+        assert_eq!(be.requests, vec!["fa", "fa"]);
+        assert_eq!(be.layout.as_deref(), Some("fa"));
+        assert!(w.take_events().is_empty());
+    }
+
+    #[test]
+    fn unconfirmed_switch_is_reported() {
+        let cfg = cfg(&[("chrome.exe", "fa")]);
+        let mut be = MockBackend::new(&["en", "fa"]);
+        let mut w = Watcher::new();
+        be.ignore_switches = usize::MAX;
+        be.focus(1, "chrome.exe");
+        assert_eq!(w.step(&mut be, &cfg), None);
+        assert_eq!(be.requests.len(), 2);
+        assert_eq!(w.layout(), Some("en"));
+        assert_eq!(
+            w.take_events(),
+            vec![LayoutEvent::SwitchUnconfirmed { app: "chrome.exe".into(), wanted: "fa".into(), actual: Some("en".into()) }]
+        );
+    }
+
+    #[test]
+    fn manual_switch_is_reported_but_our_own_is_not() {
+        let cfg = cfg(&[("chrome.exe", "fa")]);
+        let mut be = MockBackend::new(&["en", "fa"]);
+        let mut w = Watcher::new();
+        w.subscribe(&mut be);
+        be.focus(1, "chrome.exe");
+        w.step(&mut be, &cfg);
+        w.observe(&mut be, &cfg, Instant::now());
+        assert!(w.take_events().is_empty());
+
+        be.switch_manually("en");
+        w.observe(&mut be, &cfg, Instant::now());
+        assert_eq!(
+            w.take_events(),
+            vec![LayoutEvent::ManualOverride { app: Some("chrome.exe".into()), from: "fa".into(), to: "en".into() }]
+        );
+        // بدون خبر تازه چیدمان دوباره خوانده نمی‌شود
+        be.layout = Some("fa".into());
+        w.observe(&mut be, &cfg, Instant::now());
+        assert!(w.take_events().is_empty());
+    }
+
+    #[test]
+    fn manual_switch_is_polled_without_a_subscription() {
+        let cfg = cfg(&[("chrome.exe", "fa")]);
+        let mut be = MockBackend::new(&["en", "fa"]);
+        let mut w = Watcher::new();
+        be.focus(1, "chrome.exe");
+        w.step(&mut be, &cfg);
+        be.layout = Some("en".into());
+        w.observe(&mut be, &cfg, Instant::now());
+        assert_eq!(w.take_events().len(), 1);
+        assert_eq!(w.layout(), Some("en"));
+    }
+
+    #[test]
+    fn control_forwards_events_to_subscribers() {
+        let c = Control::new();
+        let rx = c.subscribe();
+        drop(c.subscribe());
+        let event = LayoutEvent::ManualOverride { app: None, from: "en".into(), to: "fa".into() };
+        c.dispatch(vec![event.clone()]);
+        assert_eq!(rx.try_recv(), Ok(event.clone()));
+        assert_eq!(c.subscribers.lock().unwrap().len(), 1);
+        assert_eq!(c.status().events, vec![event]);
+
+        let event = LayoutEvent::SwitchUnconfirmed { app: "a".into(), wanted: "fa".into(), actual: None };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"switch_unconfirmed","app":"a","wanted":"fa","actual":null}"#
+        );
+    }
+
+    #[test]
+    fn status_keeps_only_recent_events() {
+        let c = Control::new();
+        let events: Vec<_> = (0..RECENT_EVENTS + 3)
+            .map(|i| LayoutEvent::ManualOverride { app: Some(format!("app{}", i)), from: "en".into(), to: "fa".into() })
+            .collect();
+        c.dispatch(events.clone());
+        assert_eq!(c.status().events, events[3..]);
+
+        let resp = c.handle(Command::Status);
+        let txt = serde_json::to_string(&resp).unwrap();
+        let back: Response = serde_json::from_str(&txt).unwrap();
+        assert_eq!(back.status.unwrap().events.len(), RECENT_EVENTS);
+        assert_eq!(events[0].to_string(), "manual switch en -> fa in app0");
+    }
+}
@@ -0,0 +1,196 @@
+// tests/gnome_backend.rs
+// GnomeBackend روی یک session bus خصوصی (dbus-daemon --session) با سرویس ساختگی به جای افزونهٔ Window Calls؛
+// تنظیمات input-sources با backend کلید-فایل gsettings در یک پوشهٔ موقت. بدون dbus-daemon یا gsettings رد می‌شود.
+#![cfg(target_os = "linux")]
+
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use zbus::blocking::connection::Builder;
+use zbus::blocking::Connection;
+
+use lang_switcher_rust::backend::gnome::{self, GnomeBackend, InputSourceSettings};
+use lang_switcher_rust::backend::LayoutBackend;
+use lang_switcher_rust::layouts::LayoutRegistry;
+
+struct Bus {
+    daemon: Child,
+    address: String,
+}
+
+impl Bus {
+    fn start() -> Option<Self> {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let mut line = String::new();
+        BufReader::new(daemon.stdout.take()?).read_line(&mut line).ok()?;
+        Some(Self { daemon, address: line.trim().to_string() })
+    }
+
+    fn connect(&self) -> Connection {
+        Builder::address(self.address.as_str()).unwrap().build().unwrap()
+    }
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+/// همان متدهای افزونهٔ Window Calls
+struct Windows {
+    list: Arc<Mutex<Value>>,
+}
+
+#[zbus::interface(name = "org.gnome.Shell.Extensions.Windows")]
+impl Windows {
+    fn list(&self) -> String {
+        self.list.lock().unwrap().to_string()
+    }
+
+    fn get_title(&self, id: u32) -> zbus::fdo::Result<String> {
+        let list = self.list.lock().unwrap();
+        let win = list.as_array().unwrap().iter().find(|w| w["id"] == id);
+        win.map(|w| format!("title of {}", w["wm_class"].as_str().unwrap()))
+            .ok_or_else(|| zbus::fdo::Error::InvalidArgs("no such window".into()))
+    }
+}
+
+fn fake_shell(bus: &Bus, list: Value) -> (Connection, Arc<Mutex<Value>>) {
+    let list = Arc::new(Mutex::new(list));
+    let conn = Builder::address(bus.address.as_str())
+        .unwrap()
+        .name(gnome::SHELL_NAME)
+        .unwrap()
+        .serve_at(gnome::WINDOWS_PATH, Windows { list: Arc::clone(&list) })
+        .unwrap()
+        .build()
+        .unwrap();
+    (conn, list)
+}
+
+struct KeyfileSettings {
+    dir: PathBuf,
+    settings: InputSourceSettings,
+}
+
+impl KeyfileSettings {
+    fn new(tag: &str) -> Option<Self> {
+        let dir = std::env::temp_dir().join(format!("langswitcher-gnome-{}-{}", tag, std::process::id()));
+        let settings = InputSourceSettings::new().env("GSETTINGS_BACKEND", "keyfile").env("XDG_CONFIG_HOME", &dir);
+        // gsettings یا schema نصب نیست
+        settings.sources()?;
+        Some(Self { dir, settings })
+    }
+
+    fn gsettings(&self, args: &[&str]) -> String {
+        let out = Command::new("gsettings")
+            .args(args)
+            .env("GSETTINGS_BACKEND", "keyfile")
+            .env("XDG_CONFIG_HOME", &self.dir)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        String::from_utf8(out.stdout).unwrap().trim().to_string()
+    }
+}
+
+impl Drop for KeyfileSettings {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[test]
+fn focused_window_comes_from_the_shell() {
+    let Some(bus) = Bus::start() else { return };
+    let me = std::process::id();
+    let (_shell, list) = fake_shell(
+        &bus,
+        json!([
+            { "id": 7, "pid": 1, "wm_class": "foot", "focus": false },
+            { "id": 8, "pid": me, "wm_class": "editor", "title": "notes.txt", "focus": true }
+        ]),
+    );
+    let mut b = GnomeBackend::with(bus.connect(), InputSourceSettings::new()).unwrap();
+
+    let app = b.foreground_app().unwrap();
+    assert_eq!(app.pid, me);
+    assert_eq!(app.window, Some(8));
+    assert_eq!(app.title.as_deref(), Some("notes.txt"));
+    assert!(app.exe_path.is_some());
+
+    // پروسهٔ ناپیدا: نام از wm_class؛ عنوان از GetTitle
+    *list.lock().unwrap() = json!([{ "id": 9, "pid": 999_999_999u32, "wm_class": "org.gnome.Nautilus", "focus": true }]);
+    let app = b.foreground_app().unwrap();
+    assert_eq!(app.name, "org.gnome.Nautilus");
+    assert_eq!(app.title.as_deref(), Some("title of org.gnome.Nautilus"));
+
+    assert!(b.window_alive(9));
+    assert!(!b.window_alive(8));
+}
+
+#[test]
+fn connect_fails_without_the_extension() {
+    let Some(bus) = Bus::start() else { return };
+    assert!(GnomeBackend::with(bus.connect(), InputSourceSettings::new()).is_err());
+}
+
+#[test]
+fn switches_input_source_through_settings() {
+    let Some(bus) = Bus::start() else { return };
+    let Some(ks) = KeyfileSettings::new("layout") else { return };
+    ks.gsettings(&["set", "org.gnome.desktop.input-sources", "sources", "[('xkb', 'us'), ('xkb', 'ir'), ('ibus', 'anthy')]"]);
+    let (_shell, _) = fake_shell(&bus, json!([]));
+    let reg = LayoutRegistry::default();
+    let mut b = GnomeBackend::with(bus.connect(), ks.settings.clone()).unwrap();
+
+    assert_eq!(b.installed_layouts(&reg), vec!["en", "fa"]);
+    assert_eq!(b.current_layout(&reg).as_deref(), Some("en"));
+
+    assert!(b.set_layout(reg.get("fa").unwrap()));
+    assert_eq!(b.current_layout(&reg).as_deref(), Some("fa"));
+    assert_eq!(ks.gsettings(&["get", "org.gnome.desktop.input-sources", "current"]), "uint32 1");
+    assert_eq!(
+        ks.gsettings(&["get", "org.gnome.desktop.input-sources", "mru-sources"]),
+        "[('xkb', 'ir'), ('xkb', 'us'), ('ibus', 'anthy')]"
+    );
+
+    assert!(b.set_layout(reg.get("en").unwrap()));
+    assert_eq!(
+        ks.gsettings(&["get", "org.gnome.desktop.input-sources", "mru-sources"]),
+        "[('xkb', 'us'), ('xkb', 'ir'), ('ibus', 'anthy')]"
+    );
+    assert!(!b.set_layout(reg.get("ru").unwrap()));
+}
+
+#[test]
+fn layout_changes_follow_the_settings() {
+    let Some(bus) = Bus::start() else { return };
+    let Some(ks) = KeyfileSettings::new("monitor") else { return };
+    ks.gsettings(&["set", "org.gnome.desktop.input-sources", "sources", "[('xkb', 'us'), ('xkb', 'ir')]"]);
+    let (_shell, _) = fake_shell(&bus, json!([]));
+    let mut b = GnomeBackend::with(bus.connect(), ks.settings.clone()).unwrap();
+    let changes = b.layout_changes().unwrap();
+    // monitor تا آماده شدن چیزی چاپ نمی‌کند؛ تا اولین خبر دوباره بنویس
+    let mut seen = false;
+    for i in 0..50 {
+        let mru = if i % 2 == 0 { "[('xkb', 'ir'), ('xkb', 'us')]" } else { "[('xkb', 'us'), ('xkb', 'ir')]" };
+        ks.gsettings(&["set", "org.gnome.desktop.input-sources", "mru-sources", mru]);
+        if changes.recv_timeout(Duration::from_millis(100)).is_ok() {
+            seen = true;
+            break;
+        }
+    }
+    assert!(seen);
+}
@@ -0,0 +1,226 @@
+// tests/hyprland_backend.rs
+// HyprlandBackend در برابر دو سوکت ساختگی که یک جریان رویداد ضبط‌شده (fixtures/hyprland-events.txt) را پخش می‌کنند؛
+// جواب j/activewindow و j/devices از همان رویدادها ساخته می‌شود.
+#![cfg(target_os = "linux")]
+
+use std::io::{Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use lang_switcher_rust::backend::hyprland::{self, HyprlandBackend};
+use lang_switcher_rust::backend::LayoutBackend;
+use lang_switcher_rust::layouts::LayoutRegistry;
+
+const RECORDED: &str = include_str!("fixtures/hyprland-events.txt");
+
+struct State {
+    active: Value,
+    clients: Vec<String>,
+    keyboard: Value,
+    commands: Vec<String>,
+    subscribers: Vec<UnixStream>,
+}
+
+struct FakeHyprland {
+    dir: PathBuf,
+    state: Arc<Mutex<State>>,
+}
+
+impl FakeHyprland {
+    fn start(tag: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("langswitcher-hypr-{}-{}", tag, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let requests = UnixListener::bind(dir.join(".socket.sock")).unwrap();
+        let events = UnixListener::bind(dir.join(".socket2.sock")).unwrap();
+        let state = Arc::new(Mutex::new(State {
+            active: json!({}),
+            clients: Vec::new(),
+            keyboard: json!({
+                "name": "at-translated-set-2-keyboard",
+                "layout": "us,ir,us",
+                "variant": ",,dvorak",
+                "active_keymap": "English (US)",
+                "main": true,
+            }),
+            commands: Vec::new(),
+            subscribers: Vec::new(),
+        }));
+
+        let shared = Arc::clone(&state);
+        thread::spawn(move || {
+            for stream in requests.incoming() {
+                let Ok(stream) = stream else { return };
+                serve(stream, &shared);
+            }
+        });
+        let shared = Arc::clone(&state);
+        thread::spawn(move || {
+            for stream in events.incoming() {
+                let Ok(stream) = stream else { return };
+                shared.lock().unwrap().subscribers.push(stream);
+            }
+        });
+        Self { dir, state }
+    }
+
+    /// یک خط ضبط‌شده را به وضعیت اعمال و برای مشترک‌ها پخش می‌کند
+    fn replay(&self, line: &str) {
+        let mut st = self.state.lock().unwrap();
+        let (name, data) = hyprland::parse_event(line).unwrap();
+        match name {
+            "activewindow" => {
+                let (class, title) = data.split_once(',').unwrap();
+                st.active = if class.is_empty() {
+                    json!({})
+                } else {
+                    // kitty همین پروسهٔ تست است تا exe پیدا شود
+                    let pid = if class == "kitty" { std::process::id() } else { 999_999_999 };
+                    json!({ "class": class, "title": title, "pid": pid })
+                };
+            }
+            "activewindowv2" if !data.is_empty() => {
+                let addr = format!("0x{}", data);
+                st.active["address"] = json!(addr);
+                if !st.clients.contains(&addr) {
+                    st.clients.push(addr);
+                }
+            }
+            "windowtitlev2" => {
+                let (addr, title) = data.split_once(',').unwrap();
+                if st.active["address"] == format!("0x{}", addr) {
+                    st.active["title"] = json!(title);
+                }
+            }
+            "openwindow" => {
+                let addr = data.split(',').next().unwrap();
+                st.clients.push(format!("0x{}", addr));
+            }
+            "closewindow" => st.clients.retain(|c| *c != format!("0x{}", data)),
+            "activelayout" => st.keyboard["active_keymap"] = json!(data.split_once(',').unwrap().1),
+            _ => {}
+        }
+        let msg = format!("{}\n", line);
+        st.subscribers.retain_mut(|s| s.write_all(msg.as_bytes()).is_ok());
+    }
+
+    /// اتصال socket2 در thread جدا پذیرفته می‌شود؛ تا آن موقع رویدادی پخش نشود
+    fn wait_for_subscriber(&self) {
+        for _ in 0..500 {
+            if !self.state.lock().unwrap().subscribers.is_empty() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        panic!("backend never subscribed to events");
+    }
+
+    fn commands(&self) -> Vec<String> {
+        self.state.lock().unwrap().commands.clone()
+    }
+}
+
+impl Drop for FakeHyprland {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn serve(mut stream: UnixStream, state: &Mutex<State>) {
+    let mut command = String::new();
+    if stream.read_to_string(&mut command).is_err() {
+        return;
+    }
+    let mut st = state.lock().unwrap();
+    let reply = match command.as_str() {
+        "j/version" => json!({ "tag": "v0.45.0" }).to_string(),
+        "j/activewindow" => st.active.to_string(),
+        "j/clients" => Value::Array(st.clients.iter().map(|a| json!({ "address": a })).collect()).to_string(),
+        "j/devices" => {
+            let power = json!({ "name": "power-button", "layout": "us", "variant": "", "main": false });
+            json!({ "mice": [], "keyboards": [power, st.keyboard] }).to_string()
+        }
+        cmd => {
+            st.commands.push(cmd.to_string());
+            match cmd.strip_prefix("switchxkblayout at-translated-set-2-keyboard ").and_then(|n| n.parse::<usize>().ok()) {
+                Some(n) if n < 3 => {
+                    let name = ["English (US)", "Persian", "English (Dvorak)"][n];
+                    st.keyboard["active_keymap"] = json!(name);
+                    "ok".to_string()
+                }
+                _ => "error: invalid command".to_string(),
+            }
+        }
+    };
+    let _ = stream.write_all(reply.as_bytes());
+}
+
+#[test]
+fn recorded_stream_drives_focus_tracking() {
+    let hypr = FakeHyprland::start("replay");
+    let mut b = HyprlandBackend::connect_to(&hypr.dir).unwrap();
+    let reg = LayoutRegistry::default();
+    let layout_rx = b.layout_changes().unwrap();
+    hypr.wait_for_subscriber();
+
+    for line in RECORDED.lines() {
+        hypr.replay(line);
+        let (name, _) = hyprland::parse_event(line).unwrap();
+        if hyprland::is_focus_event(name) {
+            assert!(b.wait_for_focus_change(Duration::from_secs(2)), "no wake-up for {}", line);
+        } else {
+            assert!(!b.wait_for_focus_change(Duration::from_millis(30)), "spurious wake-up for {}", line);
+        }
+        let active = hypr.state.lock().unwrap().active.clone();
+        let app = b.foreground_app();
+        assert_eq!(app.as_ref().map(|a| a.title.clone()), active.get("title").map(|t| t.as_str().map(str::to_string)));
+
+        match line {
+            "windowtitlev2>>55d0c2a8e6f0,nvim README.md" => {
+                let app = app.unwrap();
+                assert_eq!(app.pid, std::process::id());
+                assert_eq!(app.window, Some(0x55d0_c2a8_e6f0));
+                assert!(app.exe_path.is_some());
+            }
+            "activelayout>>at-translated-set-2-keyboard,Persian" => {
+                assert!(layout_rx.recv_timeout(Duration::from_secs(2)).is_ok());
+                assert_eq!(b.current_layout(&reg).as_deref(), Some("fa"));
+            }
+            "activewindowv2>>55d0c2b10a20" => {
+                let app = app.unwrap();
+                assert_eq!(app.name, "firefox");
+                assert!(b.window_alive(0x55d0_c2b1_0a20));
+            }
+            "closewindow>>55d0c2b10a20" => assert!(!b.window_alive(0x55d0_c2b1_0a20)),
+            _ => {}
+        }
+    }
+    // آخر ضبط روی workspace خالی است
+    assert!(b.foreground_app().is_none());
+    // دو activelayout در ضبط بود
+    assert!(layout_rx.recv_timeout(Duration::from_secs(2)).is_ok());
+    assert!(layout_rx.try_recv().is_err());
+}
+
+#[test]
+fn switches_with_switchxkblayout_on_the_main_keyboard() {
+    let hypr = FakeHyprland::start("layout");
+    let reg = LayoutRegistry::default();
+    let mut b = HyprlandBackend::connect_to(&hypr.dir).unwrap();
+
+    assert_eq!(b.installed_layouts(&reg), vec!["en", "fa", "en-dvorak"]);
+    assert_eq!(b.current_layout(&reg).as_deref(), Some("en"));
+    assert!(b.set_layout(reg.get("en-dvorak").unwrap()));
+    assert_eq!(b.current_layout(&reg).as_deref(), Some("en-dvorak"));
+    assert!(!b.set_layout(reg.get("ru").unwrap()));
+    assert_eq!(hypr.commands(), vec!["switchxkblayout at-translated-set-2-keyboard 2"]);
+
+    // نسخه‌های جدید شمارهٔ چیدمان فعال را مستقیم می‌دهند
+    hypr.state.lock().unwrap().keyboard["active_layout_index"] = json!(1);
+    assert_eq!(b.current_layout(&reg).as_deref(), Some("fa"));
+}
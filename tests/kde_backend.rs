@@ -0,0 +1,276 @@
+// tests/kde_backend.rs
+// KdeBackend روی یک session bus خصوصی با سرویس‌های ساختگی org.kde.keyboard و org.kde.KWin؛
+// KWin ساختگی اسکریپت را فقط نگه می‌دارد و تست به جای آن Activated/Closed را صدا می‌زند. بدون dbus-daemon رد می‌شود.
+#![cfg(target_os = "linux")]
+
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use zbus::blocking::connection::Builder;
+use zbus::blocking::Connection;
+
+use lang_switcher_rust::backend::kde::{self, KdeBackend, KdePaths};
+use lang_switcher_rust::backend::LayoutBackend;
+use lang_switcher_rust::layouts::LayoutRegistry;
+
+struct Bus {
+    daemon: Child,
+    address: String,
+}
+
+impl Bus {
+    fn start() -> Option<Self> {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let mut line = String::new();
+        BufReader::new(daemon.stdout.take()?).read_line(&mut line).ok()?;
+        Some(Self { daemon, address: line.trim().to_string() })
+    }
+
+    fn connect(&self) -> Connection {
+        Builder::address(self.address.as_str()).unwrap().build().unwrap()
+    }
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+struct Keyboard {
+    layouts: Vec<(String, String, String)>,
+    current: Arc<Mutex<u32>>,
+    set_calls: Arc<Mutex<Vec<u32>>>,
+}
+
+#[zbus::interface(name = "org.kde.KeyboardLayouts")]
+impl Keyboard {
+    #[zbus(name = "getLayout")]
+    fn get_layout(&self) -> u32 {
+        *self.current.lock().unwrap()
+    }
+
+    #[zbus(name = "getLayoutsList")]
+    fn get_layouts_list(&self) -> Vec<(String, String, String)> {
+        self.layouts.clone()
+    }
+
+    #[zbus(name = "setLayout")]
+    async fn set_layout(&self, index: u32, #[zbus(signal_context)] ctxt: zbus::SignalContext<'_>) -> bool {
+        self.set_calls.lock().unwrap().push(index);
+        if index as usize >= self.layouts.len() {
+            return false;
+        }
+        *self.current.lock().unwrap() = index;
+        Self::layout_changed(&ctxt, index).await.is_ok()
+    }
+
+    #[zbus(signal, name = "layoutChanged")]
+    async fn layout_changed(ctxt: &zbus::SignalContext<'_>, index: u32) -> zbus::Result<()>;
+}
+
+#[derive(Default)]
+struct ScriptState {
+    script: Option<String>,
+    started: bool,
+    unloaded: usize,
+}
+
+struct Scripting {
+    state: Arc<Mutex<ScriptState>>,
+}
+
+#[zbus::interface(name = "org.kde.kwin.Scripting")]
+impl Scripting {
+    #[zbus(name = "loadScript")]
+    fn load_script(&self, path: String, _plugin: String) -> i32 {
+        self.state.lock().unwrap().script = std::fs::read_to_string(path).ok();
+        1
+    }
+
+    #[zbus(name = "unloadScript")]
+    fn unload_script(&self, _plugin: String) -> bool {
+        self.state.lock().unwrap().unloaded += 1;
+        true
+    }
+
+    #[zbus(name = "start")]
+    fn start(&self) {
+        self.state.lock().unwrap().started = true;
+    }
+}
+
+struct FakePlasma {
+    _conn: Connection,
+    current: Arc<Mutex<u32>>,
+    set_calls: Arc<Mutex<Vec<u32>>>,
+    script: Arc<Mutex<ScriptState>>,
+    dir: PathBuf,
+}
+
+impl FakePlasma {
+    fn start(bus: &Bus, tag: &str) -> Self {
+        let current = Arc::new(Mutex::new(0));
+        let set_calls = Arc::new(Mutex::new(Vec::new()));
+        let script = Arc::new(Mutex::new(ScriptState::default()));
+        let layouts = [("us", "", "English (US)"), ("ir", "", "Persian"), ("us", "dvorak", "English (Dvorak)")]
+            .iter()
+            .map(|(a, b, c)| (a.to_string(), b.to_string(), c.to_string()))
+            .collect();
+        let conn = Builder::address(bus.address.as_str())
+            .unwrap()
+            .name(kde::KEYBOARD_NAME)
+            .unwrap()
+            .name(kde::KWIN_NAME)
+            .unwrap()
+            .serve_at(
+                kde::LAYOUTS_PATH,
+                Keyboard { layouts, current: Arc::clone(&current), set_calls: Arc::clone(&set_calls) },
+            )
+            .unwrap()
+            .serve_at(kde::SCRIPTING_PATH, Scripting { state: Arc::clone(&script) })
+            .unwrap()
+            .build()
+            .unwrap();
+        let dir = std::env::temp_dir().join(format!("langswitcher-kde-{}-{}", tag, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        Self { _conn: conn, current, set_calls, script, dir }
+    }
+
+    fn paths(&self) -> KdePaths {
+        KdePaths { kxkbrc: self.dir.join("kxkbrc"), script: self.dir.join("focus.js") }
+    }
+
+    fn set_switch_mode(&self, mode: &str) {
+        std::fs::write(self.dir.join("kxkbrc"), format!("[Layout]\nLayoutList=us,ir,us\nSwitchMode={}\n", mode)).unwrap();
+    }
+
+    /// [نام، مسیر، interface] از خط اول اسکریپت بارگذاری‌شده
+    fn script_target(&self) -> Vec<String> {
+        let st = self.script.lock().unwrap();
+        assert!(st.started);
+        let first = st.script.as_deref().unwrap().lines().next().unwrap().to_string();
+        let json = first.trim_start_matches("const target = ").trim_end_matches(';');
+        serde_json::from_str(json).unwrap()
+    }
+
+    /// کاری که اسکریپت KWin با callDBus می‌کند
+    fn kwin_calls(&self, bus: &Bus, method: &str, payload: Value) {
+        let t = self.script_target();
+        bus.connect().call_method(Some(t[0].as_str()), t[1].as_str(), Some(t[2].as_str()), method, &payload.to_string()).unwrap();
+    }
+}
+
+impl Drop for FakePlasma {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn wait_for(mut cond: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        if cond() {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    false
+}
+
+#[test]
+fn focus_comes_from_the_kwin_script() {
+    let Some(bus) = Bus::start() else { return };
+    let plasma = FakePlasma::start(&bus, "focus");
+    let mut b = KdeBackend::with(bus.connect(), plasma.paths()).unwrap();
+    assert!(b.foreground_app().is_none());
+    assert!(!b.wait_for_focus_change(Duration::from_millis(50)));
+
+    let me = std::process::id();
+    plasma.kwin_calls(&bus, "Activated", json!({ "id": "{a}", "pid": me, "class": "editor", "caption": "notes.txt" }));
+    assert!(b.wait_for_focus_change(Duration::from_secs(2)));
+    let app = b.foreground_app().unwrap();
+    assert_eq!(app.pid, me);
+    assert_eq!(app.title.as_deref(), Some("notes.txt"));
+    let first = app.window.unwrap();
+
+    plasma.kwin_calls(&bus, "Activated", json!({ "id": "{b}", "pid": 999_999_999u32, "class": "org.kde.dolphin", "caption": "Home" }));
+    assert!(b.wait_for_focus_change(Duration::from_secs(2)));
+    assert_eq!(b.foreground_app().unwrap().name, "org.kde.dolphin");
+
+    assert!(b.window_alive(first));
+    plasma.kwin_calls(&bus, "Closed", json!({ "id": "{a}" }));
+    assert!(!b.window_alive(first));
+
+    // اسکریپت با drop شدن backend برداشته می‌شود
+    let unloaded = plasma.script.lock().unwrap().unloaded;
+    drop(b);
+    assert_eq!(plasma.script.lock().unwrap().unloaded, unloaded + 1);
+}
+
+#[test]
+fn switches_layout_and_follows_layout_changed() {
+    let Some(bus) = Bus::start() else { return };
+    let plasma = FakePlasma::start(&bus, "layout");
+    plasma.set_switch_mode("Global");
+    let reg = LayoutRegistry::default();
+    let mut b = KdeBackend::with(bus.connect(), plasma.paths()).unwrap();
+    let changes = b.layout_changes().unwrap();
+
+    assert_eq!(b.installed_layouts(&reg), vec!["en", "fa", "en-dvorak"]);
+    assert_eq!(b.current_layout(&reg).as_deref(), Some("en"));
+
+    assert!(b.set_layout(reg.get("en-dvorak").unwrap()));
+    assert_eq!(*plasma.current.lock().unwrap(), 2);
+    assert!(wait_for(|| b.current_layout(&reg).as_deref() == Some("en-dvorak")));
+
+    // کاربر خودش عوض کرد: فقط سیگنال می‌آید
+    bus.connect().emit_signal(None::<()>, kde::LAYOUTS_PATH, kde::LAYOUTS_INTERFACE, "layoutChanged", &1u32).unwrap();
+    assert!(wait_for(|| b.current_layout(&reg).as_deref() == Some("fa")));
+    // یکی برای setLayout خودمان، یکی برای تعویض دستی
+    assert!(changes.recv_timeout(Duration::from_secs(2)).is_ok());
+    assert!(changes.recv_timeout(Duration::from_secs(2)).is_ok());
+
+    assert!(!b.set_layout(reg.get("ru").unwrap()));
+    assert_eq!(*plasma.set_calls.lock().unwrap(), vec![2]);
+}
+
+#[test]
+fn per_window_policy_is_not_fought() {
+    let Some(bus) = Bus::start() else { return };
+    let plasma = FakePlasma::start(&bus, "policy");
+    plasma.set_switch_mode("Window");
+    let reg = LayoutRegistry::default();
+    let mut b = KdeBackend::with(bus.connect(), plasma.paths()).unwrap();
+    assert_eq!(b.switch_mode(), kde::SwitchMode::Window);
+
+    assert!(!b.set_layout(reg.get("fa").unwrap()));
+    assert!(plasma.set_calls.lock().unwrap().is_empty());
+
+    // per-desktop هم با هر تعویض دسکتاپ چیدمان ذخیره‌شده را برمی‌گرداند
+    plasma.set_switch_mode("Desktop");
+    assert_eq!(b.switch_mode(), kde::SwitchMode::Desktop);
+    assert!(!b.set_layout(reg.get("fa").unwrap()));
+    assert!(plasma.set_calls.lock().unwrap().is_empty());
+
+    // بعد از عوض کردن تنظیم Plasma بدون راه‌اندازی دوباره کار می‌کند
+    plasma.set_switch_mode("Global");
+    assert!(b.set_layout(reg.get("fa").unwrap()));
+    assert_eq!(*plasma.set_calls.lock().unwrap(), vec![1]);
+}
+
+#[test]
+fn connect_fails_without_plasma() {
+    let Some(bus) = Bus::start() else { return };
+    assert!(KdeBackend::with(bus.connect(), KdePaths::default()).is_err());
+}
@@ -0,0 +1,216 @@
+// tests/sway_backend.rs
+// SwayBackend در برابر یک سرور IPC ساختگی که همان پروتکل قاب‌بندی‌شدهٔ i3/sway را حرف می‌زند
+#![cfg(target_os = "linux")]
+
+use std::io::{Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use lang_switcher_rust::backend::sway::SwayBackend;
+use lang_switcher_rust::backend::LayoutBackend;
+use lang_switcher_rust::layouts::LayoutRegistry;
+
+fn write_msg(s: &mut UnixStream, kind: u32, payload: &Value) {
+    let body = payload.to_string().into_bytes();
+    let mut buf = b"i3-ipc".to_vec();
+    buf.extend_from_slice(&(body.len() as u32).to_ne_bytes());
+    buf.extend_from_slice(&kind.to_ne_bytes());
+    buf.extend_from_slice(&body);
+    s.write_all(&buf).unwrap();
+}
+
+fn read_msg(s: &mut UnixStream) -> Option<(u32, String)> {
+    let mut header = [0u8; 14];
+    s.read_exact(&mut header).ok()?;
+    assert_eq!(&header[..6], b"i3-ipc");
+    let len = u32::from_ne_bytes(header[6..10].try_into().unwrap()) as usize;
+    let kind = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+    let mut body = vec![0; len];
+    s.read_exact(&mut body).ok()?;
+    Some((kind, String::from_utf8(body).unwrap()))
+}
+
+#[derive(Default)]
+struct State {
+    tree: Value,
+    layouts: Vec<&'static str>,
+    active: usize,
+    commands: Vec<String>,
+    subscribers: Vec<UnixStream>,
+}
+
+struct FakeSway {
+    path: PathBuf,
+    state: Arc<Mutex<State>>,
+}
+
+impl FakeSway {
+    fn start(tag: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("langswitcher-sway-{}-{}", tag, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sway.sock");
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let state = Arc::new(Mutex::new(State { layouts: vec!["English (US)", "Persian"], ..State::default() }));
+
+        let shared = Arc::clone(&state);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { return };
+                let state = Arc::clone(&shared);
+                thread::spawn(move || serve(stream, state));
+            }
+        });
+        Self { path, state }
+    }
+
+    fn set_focused(&self, tree: Value) {
+        self.state.lock().unwrap().tree = tree;
+    }
+
+    fn push_window_event(&self, change: &str) {
+        let mut st = self.state.lock().unwrap();
+        for s in &mut st.subscribers {
+            write_msg(s, 0x8000_0003, &json!({ "change": change, "container": {} }));
+        }
+    }
+
+    fn push_input_event(&self, change: &str) {
+        let mut st = self.state.lock().unwrap();
+        for s in &mut st.subscribers {
+            write_msg(s, 0x8000_0015, &json!({ "change": change, "input": { "type": "keyboard" } }));
+        }
+    }
+
+    fn commands(&self) -> Vec<String> {
+        self.state.lock().unwrap().commands.clone()
+    }
+}
+
+impl Drop for FakeSway {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(self.path.parent().unwrap());
+    }
+}
+
+fn serve(mut stream: UnixStream, state: Arc<Mutex<State>>) {
+    while let Some((kind, body)) = read_msg(&mut stream) {
+        let reply = {
+            let mut st = state.lock().unwrap();
+            match kind {
+                0 => {
+                    st.commands.push(body.clone());
+                    let n = body.strip_prefix("input type:keyboard xkb_switch_layout ").and_then(|n| n.parse().ok());
+                    match n {
+                        Some(n) if n < st.layouts.len() => {
+                            st.active = n;
+                            json!([{ "success": true }])
+                        }
+                        _ => json!([{ "success": false, "error": "bad command" }]),
+                    }
+                }
+                2 => {
+                    assert_eq!(body, r#"["window","input"]"#);
+                    st.subscribers.push(stream.try_clone().unwrap());
+                    json!({ "success": true })
+                }
+                4 => st.tree.clone(),
+                100 => json!([
+                    { "identifier": "1:1:mouse", "type": "pointer" },
+                    {
+                        "identifier": "1:1:keyboard",
+                        "type": "keyboard",
+                        "xkb_layout_names": st.layouts,
+                        "xkb_active_layout_index": st.active,
+                        "xkb_active_layout_name": st.layouts[st.active],
+                    }
+                ]),
+                other => panic!("unexpected message type {}", other),
+            }
+        };
+        write_msg(&mut stream, kind, &reply);
+    }
+}
+
+fn tree_with(node: Value) -> Value {
+    json!({
+        "id": 1, "type": "root", "focused": false, "nodes": [
+            { "id": 2, "type": "output", "focused": false, "nodes": [
+                { "id": 3, "type": "workspace", "focused": false, "nodes": [
+                    { "id": 10, "pid": 1, "focused": false, "name": "other", "app_id": "foot" },
+                    node
+                ] }
+            ] }
+        ]
+    })
+}
+
+#[test]
+fn focused_window_gives_pid_title_and_id() {
+    let sway = FakeSway::start("focus");
+    let me = std::process::id();
+    sway.set_focused(tree_with(json!({ "id": 42, "pid": me, "focused": true, "name": "README.md - editor", "app_id": "editor" })));
+
+    let mut b = SwayBackend::connect_to(&sway.path).unwrap();
+    let app = b.foreground_app().unwrap();
+    assert_eq!(app.pid, me);
+    assert_eq!(app.window, Some(42));
+    assert_eq!(app.title.as_deref(), Some("README.md - editor"));
+    assert!(app.exe_path.is_some());
+
+    // پروسه‌ای که دیده نمی‌شود: نام از app_id
+    sway.set_focused(tree_with(json!({ "id": 43, "pid": 999_999_999u32, "focused": true, "app_id": "org.gnome.Nautilus" })));
+    assert_eq!(b.foreground_app().unwrap().name, "org.gnome.Nautilus");
+
+    assert!(b.window_alive(10));
+    assert!(!b.window_alive(42));
+}
+
+#[test]
+fn switches_by_layout_index() {
+    let sway = FakeSway::start("layout");
+    let reg = LayoutRegistry::default();
+    let mut b = SwayBackend::connect_to(&sway.path).unwrap();
+
+    assert_eq!(b.installed_layouts(&reg), vec!["en", "fa"]);
+    assert_eq!(b.current_layout(&reg).as_deref(), Some("en"));
+    assert!(b.set_layout(reg.get("fa").unwrap()));
+    assert_eq!(b.current_layout(&reg).as_deref(), Some("fa"));
+    assert!(!b.set_layout(reg.get("ru").unwrap()));
+    assert_eq!(sway.commands(), vec!["input type:keyboard xkb_switch_layout 1"]);
+}
+
+#[test]
+fn window_events_wake_the_watcher() {
+    let sway = FakeSway::start("events");
+    let mut b = SwayBackend::connect_to(&sway.path).unwrap();
+    assert!(!b.wait_for_focus_change(Duration::from_millis(50)));
+
+    sway.push_window_event("focus");
+    assert!(b.wait_for_focus_change(Duration::from_secs(2)));
+    // جابه‌جا شدن پنجره فوکوس را عوض نمی‌کند
+    sway.push_window_event("move");
+    assert!(!b.wait_for_focus_change(Duration::from_millis(100)));
+    sway.push_window_event("title");
+    assert!(b.wait_for_focus_change(Duration::from_secs(2)));
+}
+
+#[test]
+fn layout_changes_come_from_input_events() {
+    let sway = FakeSway::start("input");
+    let mut b = SwayBackend::connect_to(&sway.path).unwrap();
+    let rx = b.layout_changes().unwrap();
+    assert!(b.layout_changes().is_none());
+
+    sway.push_input_event("xkb_keymap");
+    sway.push_input_event("xkb_layout");
+    assert!(rx.recv_timeout(Duration::from_secs(2)).is_ok());
+    assert!(rx.try_recv().is_err());
+    // رویداد input فوکوس را عوض نمی‌کند
+    assert!(!b.wait_for_focus_change(Duration::from_millis(50)));
+}
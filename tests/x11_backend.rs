@@ -0,0 +1,125 @@
+// tests/x11_backend.rs
+// به سرور X نیاز دارند، پس پیش‌فرض ignore هستند:
+//   xvfb-run -a cargo test --test x11_backend -- --ignored
+#![cfg(target_os = "linux")]
+
+use std::process::Command;
+use std::time::Duration;
+
+use lang_switcher_rust::backend::x11::X11Backend;
+use lang_switcher_rust::backend::LayoutBackend;
+use lang_switcher_rust::layouts::LayoutRegistry;
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt, CreateWindowAux, PropMode, WindowClass};
+use x11rb::wrapper::ConnectionExt as _;
+
+/// دو گروه XKB تا جابه‌جایی واقعی بین گروه 0 و 1 دیده شود
+fn two_groups() {
+    let st = Command::new("setxkbmap").args(["-layout", "us,ir"]).status().expect("setxkbmap not installed");
+    assert!(st.success(), "setxkbmap -layout us,ir failed");
+}
+
+#[test]
+#[ignore = "needs an X server"]
+fn foreground_pid_follows_net_active_window() {
+    let (conn, screen_num) = x11rb::connect(None).unwrap();
+    let root = conn.setup().roots[screen_num].root;
+    let win = conn.generate_id().unwrap();
+    conn.create_window(0, win, root, 0, 0, 10, 10, 0, WindowClass::INPUT_OUTPUT, 0, &CreateWindowAux::new())
+        .unwrap();
+
+    let wm_pid = conn.intern_atom(false, b"_NET_WM_PID").unwrap().reply().unwrap().atom;
+    let active = conn.intern_atom(false, b"_NET_ACTIVE_WINDOW").unwrap().reply().unwrap().atom;
+    conn.change_property32(PropMode::REPLACE, win, wm_pid, AtomEnum::CARDINAL, &[std::process::id()]).unwrap();
+    conn.change_property32(PropMode::REPLACE, root, active, AtomEnum::WINDOW, &[win]).unwrap();
+    conn.sync().unwrap();
+
+    let mut x = X11Backend::connect().unwrap();
+    assert_eq!(x.active_window(), Some(win));
+    assert_eq!(x.foreground_pid(), Some(std::process::id()));
+    assert_eq!(x.foreground_app().map(|a| a.pid), Some(std::process::id()));
+}
+
+#[test]
+#[ignore = "needs an X server"]
+fn set_layout_switches_between_configured_groups() {
+    two_groups();
+    let mut x = X11Backend::connect().unwrap();
+    let reg = LayoutRegistry::default();
+    assert_eq!(x.groups(), vec![("us".to_string(), None), ("ir".to_string(), None)]);
+
+    assert!(x.lock_group(0));
+    assert_eq!(x.current_layout(&reg).as_deref(), Some("en"));
+
+    assert!(x.set_layout(reg.get("fa").unwrap()));
+    assert_eq!(x.current_group(), Some(1));
+    assert_eq!(x.current_layout(&reg).as_deref(), Some("fa"));
+
+    assert!(x.set_layout(reg.get("en").unwrap()));
+    assert_eq!(x.current_group(), Some(0));
+}
+
+#[test]
+#[ignore = "needs an X server"]
+fn unconfigured_layout_leaves_xkb_groups_alone() {
+    two_groups();
+    let mut x = X11Backend::connect().unwrap();
+    let reg = LayoutRegistry::default();
+    assert!(x.lock_group(1));
+
+    assert!(!x.set_layout(reg.get("de").unwrap()));
+    assert_eq!(x.groups().len(), 2);
+    assert_eq!(x.current_group(), Some(1));
+}
+
+#[test]
+#[ignore = "needs an X server"]
+fn active_window_change_wakes_the_backend() {
+    let mut x = X11Backend::connect().unwrap();
+    // رویدادهای قبلی (از تست‌های دیگر) را خالی کن
+    for _ in 0..10 {
+        if !x.wait_for_focus_change(Duration::from_millis(50)) {
+            break;
+        }
+    }
+
+    let (conn, screen_num) = x11rb::connect(None).unwrap();
+    let root = conn.setup().roots[screen_num].root;
+    let win = conn.generate_id().unwrap();
+    conn.create_window(0, win, root, 0, 0, 10, 10, 0, WindowClass::INPUT_OUTPUT, 0, &CreateWindowAux::new())
+        .unwrap();
+    let active = conn.intern_atom(false, b"_NET_ACTIVE_WINDOW").unwrap().reply().unwrap().atom;
+    conn.change_property32(PropMode::REPLACE, root, active, AtomEnum::WINDOW, &[win]).unwrap();
+    conn.sync().unwrap();
+
+    assert!(x.wait_for_focus_change(Duration::from_secs(2)));
+    assert!(!x.wait_for_focus_change(Duration::from_millis(100)));
+}
+
+#[test]
+#[ignore = "needs an X server"]
+fn title_of_active_window_is_reported_and_watched() {
+    let (conn, screen_num) = x11rb::connect(None).unwrap();
+    let root = conn.setup().roots[screen_num].root;
+    let win = conn.generate_id().unwrap();
+    conn.create_window(0, win, root, 0, 0, 10, 10, 0, WindowClass::INPUT_OUTPUT, 0, &CreateWindowAux::new())
+        .unwrap();
+    let wm_pid = conn.intern_atom(false, b"_NET_WM_PID").unwrap().reply().unwrap().atom;
+    let wm_name = conn.intern_atom(false, b"_NET_WM_NAME").unwrap().reply().unwrap().atom;
+    let utf8 = conn.intern_atom(false, b"UTF8_STRING").unwrap().reply().unwrap().atom;
+    let active = conn.intern_atom(false, b"_NET_ACTIVE_WINDOW").unwrap().reply().unwrap().atom;
+    conn.change_property32(PropMode::REPLACE, win, wm_pid, AtomEnum::CARDINAL, &[std::process::id()]).unwrap();
+    conn.change_property8(PropMode::REPLACE, win, wm_name, utf8, "گفتگو - Chrome".as_bytes()).unwrap();
+    conn.change_property32(PropMode::REPLACE, root, active, AtomEnum::WINDOW, &[win]).unwrap();
+    conn.sync().unwrap();
+
+    let mut x = X11Backend::connect().unwrap();
+    assert_eq!(x.foreground_app().and_then(|a| a.title).as_deref(), Some("گفتگو - Chrome"));
+    while x.wait_for_focus_change(Duration::from_millis(100)) {}
+
+    conn.change_property8(PropMode::REPLACE, win, wm_name, utf8, b"GitHub - Chrome").unwrap();
+    conn.sync().unwrap();
+    assert!(x.wait_for_focus_change(Duration::from_secs(2)));
+    assert_eq!(x.window_title(win).as_deref(), Some("GitHub - Chrome"));
+}